@@ -0,0 +1,320 @@
+//! Two-level acceleration structure.
+//!
+//! Each mesh or object gets a bottom-level BVH ([`Blas`]) over its primitives,
+//! built once and shared by every [`Instance`] that places it. The top-level
+//! BVH ([`Tlas`]) only covers the instances' world-space boxes, so moving
+//! instances between frames means rebuilding a tree over one box per instance
+//! and never touching the geometry. Memory grows with the unique meshes plus
+//! a transform per instance.
+//!
+//! Both levels use the same flattened BVH: nodes in one array, depth first,
+//! traversed with a fixed stack and nearest child first.
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::transform::{self, Transform};
+use crate::vec3::Vec3;
+use std::sync::Arc;
+
+/// Leaves hold at most this many items.
+const MAX_LEAF_ITEMS: usize = 4;
+
+/// Beyond this depth splits fall back to the median, which bounds the depth
+/// of the tree, and so the traversal stack, whatever the input.
+const MAX_SAH_DEPTH: usize = 32;
+
+const STACK_SIZE: usize = MAX_SAH_DEPTH + 32;
+
+/// Number of candidate split planes per axis evaluated by the SAH.
+const SAH_BINS: usize = 12;
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    bbox: Aabb,
+    /// For leaves the first entry in `Bvh::items`; for interior nodes the
+    /// second child, the first one following the node directly.
+    offset: u32,
+    /// Items in a leaf, zero for interior nodes.
+    count: u32,
+    /// Axis along which the second child lies beyond the first.
+    axis: u8,
+}
+
+/// A flattened BVH over item indices.
+#[derive(Clone, Debug, Default)]
+struct Bvh {
+    nodes: Vec<Node>,
+    items: Vec<u32>,
+}
+
+impl Bvh {
+    fn build(mut bounds: Vec<(u32, Aabb)>) -> Self {
+        let mut nodes = Vec::new();
+        if !bounds.is_empty() {
+            build_node(&mut bounds, 0, 0, &mut nodes);
+        }
+        Self { nodes, items: bounds.into_iter().map(|(item, _)| item).collect() }
+    }
+
+    fn bbox(&self) -> Option<Aabb> {
+        self.nodes.first().map(|n| n.bbox)
+    }
+
+    /// Closest hit among the items, where `hit_item(item, t_max)` intersects one.
+    fn hit(&self, r: &Ray, t_min: f64, mut t_max: f64, mut hit_item: impl FnMut(u32, f64) -> Option<HitRecord>) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let negative = [r.direction.x < 0.0, r.direction.y < 0.0, r.direction.z < 0.0];
+        let mut stack = [0u32; STACK_SIZE];
+        let mut len = 0;
+        let mut current = 0;
+        let mut closest = None;
+        loop {
+            let node = &self.nodes[current];
+            if node.bbox.hit(r, t_min, t_max) {
+                if node.count == 0 {
+                    let (first, second) = (current + 1, node.offset as usize);
+                    let (near, far) = if negative[node.axis as usize] { (second, first) } else { (first, second) };
+                    stack[len] = far as u32;
+                    len += 1;
+                    current = near;
+                    continue;
+                }
+                let start = node.offset as usize;
+                for &item in &self.items[start..start + node.count as usize] {
+                    if let Some(rec) = hit_item(item, t_max) {
+                        t_max = rec.t;
+                        closest = Some(rec);
+                    }
+                }
+            }
+            if len == 0 {
+                return closest;
+            }
+            len -= 1;
+            current = stack[len] as usize;
+        }
+    }
+}
+
+/// Appends the subtree over `items`, which start at `start` in the final
+/// item order, to `nodes`.
+fn build_node(items: &mut [(u32, Aabb)], start: usize, depth: usize, nodes: &mut Vec<Node>) {
+    let bbox = items.iter().skip(1).fold(items[0].1, |acc, (_, b)| acc.surrounding(b));
+    let index = nodes.len();
+    nodes.push(Node { bbox, offset: start as u32, count: items.len() as u32, axis: 0 });
+    if items.len() <= MAX_LEAF_ITEMS {
+        return;
+    }
+
+    let n = items.len();
+    let mid = match sah_split(items) {
+        Some(mid) if depth < MAX_SAH_DEPTH => mid,
+        _ => n / 2,
+    };
+    let (left, right) = items.split_at_mut(mid);
+    let gap = centroid(right) - centroid(left);
+    let axis = (0..3).max_by(|&a, &b| gap[a].total_cmp(&gap[b])).unwrap_or(0);
+
+    build_node(left, start, depth + 1, nodes);
+    let second = nodes.len() as u32;
+    build_node(right, start + mid, depth + 1, nodes);
+    nodes[index] = Node { bbox, offset: second, count: 0, axis: axis as u8 };
+}
+
+fn centroid(items: &[(u32, Aabb)]) -> Vec3 {
+    items.iter().fold(Vec3::new(0.0, 0.0, 0.0), |acc, (_, b)| acc + b.centroid()) / items.len() as f64
+}
+
+/// Sorts `items` along the best axis and returns the split index minimising the
+/// SAH cost, or `None` when all centroids coincide (after sorting along x).
+fn sah_split<T>(items: &mut [(T, Aabb)]) -> Option<usize> {
+    let centroid_bounds = items
+        .iter()
+        .skip(1)
+        .fold(Aabb::new(items[0].1.centroid(), items[0].1.centroid()), |acc, (_, b)| {
+            acc.surrounding(&Aabb::new(b.centroid(), b.centroid()))
+        });
+
+    // (cost, axis, number of items on the left)
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        let lo = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - lo;
+        if extent <= 0.0 {
+            continue;
+        }
+
+        let mut counts = [0usize; SAH_BINS];
+        let mut bounds: [Option<Aabb>; SAH_BINS] = [None; SAH_BINS];
+        for (_, b) in items.iter() {
+            let bin = bin_index(b.centroid()[axis], lo, extent);
+            counts[bin] += 1;
+            bounds[bin] = Some(bounds[bin].map_or(*b, |acc| acc.surrounding(b)));
+        }
+
+        // Sweep from the right to get the area of every suffix of bins.
+        let mut right_area = [0.0; SAH_BINS];
+        let mut acc: Option<Aabb> = None;
+        for i in (1..SAH_BINS).rev() {
+            acc = merge(acc, bounds[i]);
+            right_area[i] = acc.map_or(0.0, |b| b.surface_area());
+        }
+
+        let mut left_box: Option<Aabb> = None;
+        let mut left_count = 0;
+        for i in 0..SAH_BINS - 1 {
+            left_box = merge(left_box, bounds[i]);
+            left_count += counts[i];
+            let right_count = items.len() - left_count;
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = left_count as f64 * left_box.map_or(0.0, |b| b.surface_area())
+                + right_count as f64 * right_area[i + 1];
+            if best.is_none_or(|(c, _, _)| cost < c) {
+                best = Some((cost, axis, left_count));
+            }
+        }
+    }
+
+    let axis = best.map_or(0, |(_, axis, _)| axis);
+    items.sort_by(|a, b| a.1.centroid()[axis].total_cmp(&b.1.centroid()[axis]));
+    best.map(|(_, _, left_count)| left_count)
+}
+
+fn bin_index(value: f64, lo: f64, extent: f64) -> usize {
+    (((value - lo) / extent * SAH_BINS as f64) as usize).min(SAH_BINS - 1)
+}
+
+fn merge(acc: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    match (acc, b) {
+        (Some(a), Some(b)) => Some(a.surrounding(&b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Bottom-level BVH over the primitives of one mesh or object.
+pub struct Blas {
+    objects: Vec<Arc<dyn Hittable>>,
+    bvh: Bvh,
+}
+
+impl Blas {
+    /// Panics if an object has no bounding box.
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Self {
+        let bounds = objects
+            .iter()
+            .enumerate()
+            .map(|(i, o)| (i as u32, o.bounding_box().expect("BLAS objects must have a bounding box")))
+            .collect();
+        Self { bvh: Bvh::build(bounds), objects }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl Hittable for Blas {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.hit(r, t_min, t_max, |i, t_max| self.objects[i as usize].hit(r, t_min, t_max))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bbox()
+    }
+}
+
+/// One placement of a possibly shared object.
+#[derive(Clone)]
+pub struct Instance {
+    pub object: Arc<dyn Hittable>,
+    /// `None` for objects already in world space.
+    pub transform: Option<Transform>,
+    /// Stamped onto hits unless zero, like [`crate::hittable::Tagged`].
+    pub object_id: u32,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Option<Transform>, object_id: u32) -> Self {
+        Self { object, transform, object_id }
+    }
+
+    /// World-space box, or `None` if the object is unbounded.
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        let b = self.object.bounding_box()?;
+        Some(self.transform.map_or(b, |t| t.bounding_box(&b)))
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut rec = match &self.transform {
+            Some(t) => transform::hit_transformed(self.object.as_ref(), t, r, t_min, t_max),
+            None => self.object.hit(r, t_min, t_max),
+        }?;
+        if self.object_id != 0 {
+            rec.object_id = self.object_id;
+        }
+        Some(rec)
+    }
+}
+
+/// Top-level BVH over instances. Unbounded instances are kept beside the
+/// tree and tested linearly.
+#[derive(Clone, Default)]
+pub struct Tlas {
+    /// Changes take effect on the next [`Tlas::rebuild`].
+    pub instances: Vec<Instance>,
+    bounds: Vec<Option<Aabb>>,
+    unbounded: Vec<u32>,
+    bvh: Bvh,
+}
+
+impl Tlas {
+    pub fn new(instances: Vec<Instance>) -> Self {
+        let mut tlas = Self { instances, ..Self::default() };
+        tlas.rebuild();
+        tlas
+    }
+
+    /// Rebuilds the tree over the instances' current boxes, e.g. once per
+    /// frame after moving them. The instanced objects are not touched.
+    pub fn rebuild(&mut self) {
+        self.bounds = self.instances.iter().map(Instance::bounding_box).collect();
+        self.unbounded = (0..self.instances.len() as u32).filter(|&i| self.bounds[i as usize].is_none()).collect();
+        self.bvh = Bvh::build(self.bounds.iter().enumerate().filter_map(|(i, b)| Some((i as u32, (*b)?))).collect());
+    }
+}
+
+impl Hittable for Tlas {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = self.bvh.hit(r, t_min, t_max, |i, t_max| {
+            let bbox = self.bounds[i as usize].as_ref()?;
+            if !bbox.hit(r, t_min, t_max) {
+                return None;
+            }
+            self.instances[i as usize].hit(r, t_min, t_max)
+        });
+        for &i in &self.unbounded {
+            let t_max = closest.as_ref().map_or(t_max, |h| h.t);
+            if let Some(rec) = self.instances[i as usize].hit(r, t_min, t_max) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            self.bvh.bbox()
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Arbitrary output variables: auxiliary passes computed from the primary hit
+//! of every pixel, for denoising and compositing.
+
+use crate::framebuffer::{Framebuffer, OutputFormat};
+use crate::hittable::Hittable;
+use crate::ray::Ray;
+use crate::scene::Scene;
+use crate::tonemap::{srgb_to_linear, DisplayTransform};
+use crate::vec3::{Color, Point3, Vec3};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Aov {
+    /// Distance from the camera to the first hit; infinite where nothing was hit.
+    Depth,
+    /// World-space shading normal, facing the camera.
+    Normal,
+    /// Surface color without lighting; the background color where nothing was hit.
+    Albedo,
+    /// 1-based index of the scene object hit, 0 for none.
+    ObjectId,
+    /// 1-based index of the material hit, 0 for none.
+    MaterialId,
+    /// World-space hit position.
+    Position,
+    /// Surface texture coordinates.
+    Uv,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 7] = [Aov::Depth, Aov::Normal, Aov::Albedo, Aov::ObjectId, Aov::MaterialId, Aov::Position, Aov::Uv];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object-id",
+            Aov::MaterialId => "material-id",
+            Aov::Position => "position",
+            Aov::Uv => "uv",
+        }
+    }
+
+    /// Channel names within the pass's EXR layer, taken from the x, y, z components in order.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Uv => &["U", "V"],
+        }
+    }
+
+    fn is_id(self) -> bool {
+        matches!(self, Aov::ObjectId | Aov::MaterialId)
+    }
+}
+
+impl fmt::Display for Aov {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Aov::ALL.into_iter().find(|a| a.name() == s).ok_or_else(|| {
+            let names: Vec<&str> = Aov::ALL.iter().map(|a| a.name()).collect();
+            format!("unknown AOV '{}' (expected one of: {})", s, names.join(", "))
+        })
+    }
+}
+
+/// One rendered pass. Scalar passes are stored in every component.
+#[derive(Clone, Debug)]
+pub struct AovImage {
+    pub aov: Aov,
+    pub image: Framebuffer,
+}
+
+impl AovImage {
+    /// Remaps the raw data into a viewable image for 8-bit outputs: depth as
+    /// near-white to far-black, normals as `n * 0.5 + 0.5`, positions normalized
+    /// to their range, UVs wrapped to [0, 1) and IDs as arbitrary distinct colors.
+    fn visualize(&self) -> Framebuffer {
+        let pixels = &self.image.pixels;
+        let range = |c: usize| {
+            let finite = pixels.iter().map(|p| p[c]).filter(|x| x.is_finite());
+            finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| (lo.min(x), hi.max(x)))
+        };
+        let normalize = |x: f64, (lo, hi): (f64, f64)| if hi > lo { (x - lo) / (hi - lo) } else { 1.0 };
+        // Data passes are shown as-is, so undo the display encoding applied on save.
+        let data = |x: f64, y: f64, z: f64| Color::new(srgb_to_linear(x), srgb_to_linear(y), srgb_to_linear(z));
+
+        let mut out = self.image.clone();
+        match self.aov {
+            Aov::Depth => {
+                let depth_range = range(0);
+                for p in &mut out.pixels {
+                    let d = if p.x.is_finite() { 1.0 - normalize(p.x, depth_range) } else { 0.0 };
+                    *p = data(d, d, d);
+                }
+            }
+            Aov::Normal => {
+                for p in &mut out.pixels {
+                    let n = *p * 0.5 + Color::new(0.5, 0.5, 0.5);
+                    *p = data(n.x, n.y, n.z);
+                }
+            }
+            Aov::Albedo => {}
+            Aov::ObjectId | Aov::MaterialId => {
+                for p in &mut out.pixels {
+                    *p = id_color(p.x as u32);
+                }
+            }
+            Aov::Position => {
+                let ranges = [range(0), range(1), range(2)];
+                for p in &mut out.pixels {
+                    *p = data(normalize(p.x, ranges[0]), normalize(p.y, ranges[1]), normalize(p.z, ranges[2]));
+                }
+            }
+            Aov::Uv => {
+                for p in &mut out.pixels {
+                    *p = data(p.x.rem_euclid(1.0), p.y.rem_euclid(1.0), 0.0);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// A stable, well-spread color for an ID; black for 0.
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::zero();
+    }
+    let h = id.wrapping_mul(0x9E37_79B9).rotate_left(7) ^ id.wrapping_mul(0x85EB_CA6B);
+    let [r, g, b, _] = h.to_be_bytes();
+    Color::from_rgb8([r | 0x40, g | 0x40, b | 0x40])
+}
+
+/// Running sums of the primary-hit data of one pixel.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AovAccumulator {
+    samples: u32,
+    hits: u32,
+    depth: f64,
+    normal: Vec3,
+    albedo: Color,
+    position: Point3,
+    uv: Vec3,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl AovAccumulator {
+    /// Adds the first hit along the camera ray `r`.
+    pub(crate) fn add(&mut self, r: &Ray, scene: &Scene) {
+        self.samples += 1;
+        match scene.world.hit(r, 0.001, f64::INFINITY) {
+            Some(rec) => {
+                self.hits += 1;
+                self.depth += rec.t * r.direction.length();
+                self.normal += rec.normal;
+                self.albedo += rec.mat.albedo(&rec);
+                self.position += rec.p;
+                self.uv += Vec3::new(rec.u, rec.v, 0.0);
+            }
+            None => self.albedo += scene.background.color(r),
+        }
+    }
+
+    /// Takes the IDs from the first hit along `r`; IDs are never averaged.
+    pub(crate) fn set_ids(&mut self, r: &Ray, scene: &Scene) {
+        let rec = scene.world.hit(r, 0.001, f64::INFINITY);
+        self.object_id = rec.as_ref().map_or(0, |rec| rec.object_id);
+        self.material_id = rec.as_ref().map_or(0, |rec| rec.material_id);
+    }
+
+    /// The pass value: averaged over all samples for normal and albedo, over the
+    /// samples that hit something for depth, position and UV.
+    pub(crate) fn value(&self, aov: Aov) -> Color {
+        let per_sample = |v: Vec3| if self.samples > 0 { v / self.samples as f64 } else { Vec3::zero() };
+        let per_hit = |v: Vec3| if self.hits > 0 { v / self.hits as f64 } else { Vec3::zero() };
+        let scalar = |x: f64| Color::new(x, x, x);
+        match aov {
+            Aov::Depth if self.hits == 0 => scalar(f64::INFINITY),
+            Aov::Depth => scalar(self.depth / self.hits as f64),
+            Aov::Normal => per_sample(self.normal),
+            Aov::Albedo => per_sample(self.albedo),
+            Aov::ObjectId => scalar(self.object_id as f64),
+            Aov::MaterialId => scalar(self.material_id as f64),
+            Aov::Position => per_hit(self.position),
+            Aov::Uv => per_hit(self.uv),
+        }
+    }
+}
+
+/// Path of the sibling file holding `aov` for an output written to `path`:
+/// `render.png` becomes `render.depth.png`.
+pub fn aov_path(path: impl AsRef<Path>, aov: Aov) -> PathBuf {
+    let path = path.as_ref();
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("render");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}.{}", stem, aov.name(), ext),
+        None => format!("{}.{}", stem, aov.name()),
+    };
+    path.with_file_name(name)
+}
+
+/// Writes the beauty image together with its AOVs. An `.exr` output holds
+/// every pass in one file, as `<aov>.<channel>` channels next to `R`, `G`
+/// and `B`; other formats write each pass to [`aov_path`]. Float formats keep
+/// the raw data, 8-bit formats get the visualization of each pass.
+pub fn save(path: impl AsRef<Path>, beauty: &Framebuffer, aovs: &[AovImage], display: &DisplayTransform) -> image::ImageResult<()> {
+    let path = path.as_ref();
+    match OutputFormat::from_path(path) {
+        OutputFormat::Exr if !aovs.is_empty() => write_exr(path, beauty, aovs).map_err(|e| io::Error::other(e).into()),
+        OutputFormat::Exr | OutputFormat::Hdr | OutputFormat::Pfm => {
+            beauty.save(path, display)?;
+            for aov in aovs {
+                aov.image.save(aov_path(path, aov.aov), display)?;
+            }
+            Ok(())
+        }
+        OutputFormat::Ldr => {
+            beauty.save(path, display)?;
+            for aov in aovs {
+                aov.visualize().save(aov_path(path, aov.aov), &DisplayTransform::default())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Single-part EXR with 32-bit float channels, except for the IDs which are stored as integers.
+fn write_exr(path: &Path, beauty: &Framebuffer, aovs: &[AovImage]) -> exr::error::Result<()> {
+    use exr::prelude::*;
+
+    let float_channel = |name: &str, image: &Framebuffer, c: usize| {
+        AnyChannel::new(name, FlatSamples::F32(image.pixels.iter().map(|p| p[c] as f32).collect()))
+    };
+
+    let mut channels: SmallVec<[AnyChannel<FlatSamples>; 4]> = SmallVec::new();
+    for (c, name) in ["R", "G", "B"].into_iter().enumerate() {
+        channels.push(float_channel(name, beauty, c));
+    }
+    for pass in aovs {
+        for (c, channel) in pass.aov.channels().iter().enumerate() {
+            let name = format!("{}.{}", pass.aov.name(), channel);
+            channels.push(if pass.aov.is_id() {
+                AnyChannel::new(name.as_str(), FlatSamples::U32(pass.image.pixels.iter().map(|p| p[c] as u32).collect()))
+            } else {
+                float_channel(&name, &pass.image, c)
+            });
+        }
+    }
+
+    let size = (beauty.width as usize, beauty.height as usize);
+    let layer = Layer::new(size, LayerAttributes::default(), Encoding::FAST_LOSSLESS, AnyChannels::sort(channels));
+    Image::from_layer(layer).write().to_file(path)
+}
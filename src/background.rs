@@ -0,0 +1,35 @@
+use crate::ray::Ray;
+use crate::vec3::Color;
+
+pub const SKY_HORIZON: Color = Color::new(1.0, 1.0, 1.0);
+pub const SKY_ZENITH: Color = Color::new(0.5, 0.7, 1.0);
+
+/// Radiance returned for rays that escape the scene.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    /// Vertical blend from `horizon` (looking down) to `zenith` (looking up).
+    Gradient { horizon: Color, zenith: Color },
+    Solid(Color),
+}
+
+impl Background {
+    /// The default white-to-blue sky.
+    pub const SKY: Background = Background::Gradient { horizon: SKY_HORIZON, zenith: SKY_ZENITH };
+
+    pub fn color(&self, r: &Ray) -> Color {
+        match *self {
+            Background::Gradient { horizon, zenith } => {
+                let unit_direction = r.direction.unit_vector();
+                let t = 0.5 * (unit_direction.y + 1.0);
+                horizon * (1.0 - t) + zenith * t
+            }
+            Background::Solid(color) => color,
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::SKY
+    }
+}
@@ -1,5 +1,6 @@
 use crate::vec3::{Point3, Vec3};
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 pub struct Camera {
     origin: Point3,
@@ -8,8 +9,10 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
     lens_radius: f64,
+    /// Shutter interval; rays get a time spread uniformly over it.
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
@@ -35,17 +38,33 @@ impl Camera {
             vertical,
             u,
             v,
-            w,
             lens_radius: aperture / 2.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = Vec3::random_in_unit_sphere() * self.lens_radius;
+    /// Keeps the shutter open from `open` to `close`, blurring whatever
+    /// moves in between.
+    pub fn with_shutter(self, open: f64, close: f64) -> Self {
+        Self { shutter_open: open, shutter_close: close, ..self }
+    }
+
+    /// Ray through viewport coordinates (`s`, `t`); `sampler` picks the point
+    /// on the lens and, if the shutter is open for a while, the time.
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Ray {
+        let rd = Vec3::sample_unit_disk(sampler.get_2d()) * self.lens_radius;
         let offset = self.u * rd.x + self.v * rd.y;
-        Ray::new(
+        // An instantaneous shutter draws no sample, leaving still renders unchanged.
+        let time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + (self.shutter_close - self.shutter_open) * sampler.get_1d()
+        } else {
+            self.shutter_open
+        };
+        Ray::with_time(
             self.origin + offset,
             self.lower_left_corner + self.horizontal * s + self.vertical * t - self.origin - offset,
+            time,
         )
     }
 }
@@ -0,0 +1,198 @@
+//! Render checkpoints.
+//!
+//! A checkpoint stores a [`RenderState`]: the film's weighted sums and the
+//! sample statistics of every pixel, little-endian, behind a header holding
+//! the image size and a hash of everything that determines the samples. The
+//! sample count is left out of that hash where it can be, so a checkpoint can
+//! be resumed with more samples per pixel than it was started with.
+
+use crate::background::Background;
+use crate::render::{PixelStats, RenderSettings, RenderState};
+use crate::sampler::SamplerKind;
+use crate::scene::Scene;
+use crate::vec3::Color;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"RTCKPT01";
+
+/// 64-bit FNV-1a; stable across builds, unlike `std`'s default hasher.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Identifies the scene and the settings a checkpoint's samples were taken
+/// with. Samples per pixel and the adaptive-sampling parameters are excluded
+/// since they only decide how many samples a pixel gets, except for the
+/// stratified sampler, whose strata are laid out for that many samples.
+/// `None` if the scene has no [`Scene::source_hash`] to identify it by.
+pub fn settings_hash(settings: &RenderSettings, scene: &Scene) -> Option<u64> {
+    let mut bytes = Vec::new();
+    bytes.extend(scene.source_hash?.to_le_bytes());
+    let colors = match scene.background {
+        Background::Gradient { horizon, zenith } => {
+            bytes.push(0);
+            vec![horizon, zenith]
+        }
+        Background::Solid(color) => {
+            bytes.push(1);
+            vec![color]
+        }
+    };
+    for c in colors {
+        for v in [c.x, c.y, c.z] {
+            bytes.extend(v.to_bits().to_le_bytes());
+        }
+    }
+    for v in [settings.width, settings.height, settings.max_depth] {
+        bytes.extend(v.to_le_bytes());
+    }
+    bytes.extend(settings.seed.to_le_bytes());
+    bytes.extend(settings.sampler.name().bytes());
+    if settings.sampler == SamplerKind::Stratified {
+        bytes.extend(settings.samples_per_pixel.to_le_bytes());
+    }
+    bytes.extend(settings.filter.kind.name().bytes());
+    bytes.extend(settings.filter.radius.to_bits().to_le_bytes());
+    Some(fnv1a(&bytes))
+}
+
+/// [`settings_hash`], or an error for scenes that can't be identified.
+fn required_settings_hash(settings: &RenderSettings, scene: &Scene) -> io::Result<u64> {
+    settings_hash(settings, scene).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "scene has no source hash to identify it in a checkpoint")
+    })
+}
+
+impl RenderState {
+    /// Writes the state to `path`. The file is written next to it first and
+    /// then renamed, so an interrupted write leaves the previous checkpoint.
+    /// Scenes without a [`Scene::source_hash`] fail with [`io::ErrorKind::InvalidInput`].
+    pub fn save(&self, path: impl AsRef<Path>, settings: &RenderSettings, scene: &Scene) -> io::Result<()> {
+        let path = path.as_ref();
+        let hash = required_settings_hash(settings, scene)?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        w.write_all(MAGIC)?;
+        w.write_all(&hash.to_le_bytes())?;
+        w.write_all(&self.film.width.to_le_bytes())?;
+        w.write_all(&self.film.height.to_le_bytes())?;
+        for ((sum, weight), stats) in self.film.sums.iter().zip(&self.film.weights).zip(&self.stats) {
+            for v in [sum.x, sum.y, sum.z, *weight] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            w.write_all(&stats.count.to_le_bytes())?;
+            w.write_all(&stats.mean.to_le_bytes())?;
+            w.write_all(&stats.m2.to_le_bytes())?;
+        }
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Reads a checkpoint written by [`RenderState::save`] for the same scene
+    /// and settings; anything else is rejected with [`io::ErrorKind::InvalidData`],
+    /// and scenes without a [`Scene::source_hash`] with [`io::ErrorKind::InvalidInput`].
+    pub fn load(path: impl AsRef<Path>, settings: &RenderSettings, scene: &Scene) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let hash = required_settings_hash(settings, scene)?;
+        let mut r = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a render checkpoint"));
+        }
+        if read_u64(&mut r)? != hash {
+            return Err(invalid("checkpoint was rendered with a different scene or settings"));
+        }
+        if read_u32(&mut r)? != settings.width || read_u32(&mut r)? != settings.height {
+            return Err(invalid("checkpoint has a different image size"));
+        }
+
+        let mut state = RenderState::new(settings);
+        for ((sum, weight), stats) in state.film.sums.iter_mut().zip(&mut state.film.weights).zip(&mut state.stats) {
+            *sum = Color::new(read_f64(&mut r)?, read_f64(&mut r)?, read_f64(&mut r)?);
+            *weight = read_f64(&mut r)?;
+            *stats = PixelStats { count: read_u32(&mut r)?, mean: read_f64(&mut r)?, m2: read_f64(&mut r)? };
+        }
+        if r.read(&mut [0])? != 0 {
+            return Err(invalid("trailing data after checkpoint"));
+        }
+        Ok(state)
+    }
+}
+
+pub(crate) fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_f64(r: &mut impl Read) -> io::Result<f64> {
+    read_u64(r).map(f64::from_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::DEFAULT_SCENE;
+
+    fn settings() -> RenderSettings {
+        RenderSettings { width: 3, height: 2, samples_per_pixel: 4, seed: 7, ..Default::default() }
+    }
+
+    fn kind(result: io::Result<RenderState>) -> io::ErrorKind {
+        result.err().expect("load should fail").kind()
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let scene = Scene::parse(DEFAULT_SCENE).unwrap();
+        let settings = settings();
+        let mut state = RenderState::new(&settings);
+        for (i, ((sum, weight), stats)) in
+            state.film.sums.iter_mut().zip(&mut state.film.weights).zip(&mut state.stats).enumerate()
+        {
+            let v = i as f64;
+            *sum = Color::new(v, 0.5 * v, -v);
+            *weight = 1.0 + v;
+            *stats = PixelStats { count: 4 + i as u32, mean: 0.25 * v, m2: 0.1 * v };
+        }
+
+        let path = std::env::temp_dir().join(format!("ray-tracer-checkpoint-{}.bin", std::process::id()));
+        state.save(&path, &settings, &scene).unwrap();
+        let loaded = RenderState::load(&path, &settings, &scene).unwrap();
+        assert_eq!(loaded.film.sums, state.film.sums);
+        assert_eq!(loaded.film.weights, state.film.weights);
+        for (a, b) in loaded.stats.iter().zip(&state.stats) {
+            assert_eq!((a.count, a.mean, a.m2), (b.count, b.mean, b.m2));
+        }
+
+        // More samples per pixel resume the render, anything else is refused.
+        let more = RenderSettings { samples_per_pixel: 16, ..settings.clone() };
+        assert!(RenderState::load(&path, &more, &scene).is_ok());
+        let reseeded = RenderSettings { seed: 8, ..settings.clone() };
+        assert_eq!(kind(RenderState::load(&path, &reseeded, &scene)), io::ErrorKind::InvalidData);
+        let halton = RenderSettings { sampler: SamplerKind::Halton, ..settings.clone() };
+        assert_eq!(kind(RenderState::load(&path, &halton, &scene)), io::ErrorKind::InvalidData);
+        let resized = RenderSettings { width: 4, ..settings.clone() };
+        assert_eq!(kind(RenderState::load(&path, &resized, &scene)), io::ErrorKind::InvalidData);
+
+        // The strata of the stratified sampler depend on the sample count.
+        let stratified = RenderSettings { sampler: SamplerKind::Stratified, ..settings.clone() };
+        state.save(&path, &stratified, &scene).unwrap();
+        assert!(RenderState::load(&path, &stratified, &scene).is_ok());
+        let more = RenderSettings { samples_per_pixel: 16, ..stratified };
+        assert_eq!(kind(RenderState::load(&path, &more, &scene)), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
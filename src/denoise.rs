@@ -0,0 +1,141 @@
+//! Edge-avoiding À-trous wavelet denoiser (Dammertz et al. 2010).
+//!
+//! The noisy radiance is filtered with a 5×5 B3-spline kernel whose taps are
+//! spread further apart on every pass. Each tap is weighted by how similar it
+//! is to the center pixel in color, albedo, normal and depth, so the blur
+//! stops at geometric and material edges. Lighting is filtered with the
+//! albedo divided out and multiplied back in afterwards, which keeps textures
+//! sharp.
+
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Color;
+use rayon::prelude::*;
+
+/// The 1D B3-spline kernel. Taps are weighted by the outer product `h[x] · h[y]`
+/// over the full 5×5 window, since the edge-stopping weights aren't separable.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Albedo channels below this are left out of the demodulation (emitters, black surfaces).
+const MIN_ALBEDO: f64 = 1e-3;
+
+/// Filter parameters. Larger sigmas blur more across the corresponding edges.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Denoiser {
+    /// Filter passes; pass `i` spaces its taps `2^i` pixels apart.
+    pub iterations: u32,
+    /// Tolerance for color differences, measured after compressing radiance
+    /// with `x / (1 + x)`. Halved every pass so the detail left by early
+    /// passes is preserved.
+    pub sigma_color: f64,
+    pub sigma_normal: f64,
+    pub sigma_albedo: f64,
+    /// Tolerance for depth differences, relative to the center pixel's depth.
+    pub sigma_depth: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self { iterations: 5, sigma_color: 0.6, sigma_normal: 0.3, sigma_albedo: 0.1, sigma_depth: 0.05 }
+    }
+}
+
+/// Guide data for one pixel.
+#[derive(Copy, Clone)]
+struct Feature {
+    albedo: Color,
+    normal: Color,
+    depth: f64,
+}
+
+impl Denoiser {
+    /// Denoises `beauty` using the albedo, normal and depth passes rendered for it
+    /// (see [`crate::Renderer::render_aovs`]). All buffers must have the same size.
+    pub fn denoise(&self, beauty: &Framebuffer, albedo: &Framebuffer, normal: &Framebuffer, depth: &Framebuffer) -> Framebuffer {
+        for guide in [albedo, normal, depth] {
+            assert!(guide.width == beauty.width && guide.height == beauty.height, "guide buffers must match the image size");
+        }
+
+        let features: Vec<Feature> = (0..beauty.pixels.len())
+            .map(|i| Feature { albedo: albedo.pixels[i], normal: normal.pixels[i], depth: depth.pixels[i].x })
+            .collect();
+
+        let mut image = beauty.clone();
+        for (p, f) in image.pixels.iter_mut().zip(&features) {
+            *p = demodulate(*p, f.albedo);
+        }
+        for i in 0..self.iterations {
+            image = self.pass(&image, &features, 1 << i, self.sigma_color / (1 << i) as f64);
+        }
+        for (p, f) in image.pixels.iter_mut().zip(&features) {
+            *p = remodulate(*p, f.albedo);
+        }
+        image
+    }
+
+    fn pass(&self, image: &Framebuffer, features: &[Feature], step: i64, sigma_color: f64) -> Framebuffer {
+        let (width, height) = (image.width as i64, image.height as i64);
+        let pixels = (0..height)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                (0..width).map(move |x| {
+                    let center = (y * width + x) as usize;
+                    let c_p = compress(image.pixels[center]);
+                    let f_p = features[center];
+
+                    let mut sum = Color::zero();
+                    let mut total = 0.0;
+                    for (ky, hy) in KERNEL.iter().enumerate() {
+                        for (kx, hx) in KERNEL.iter().enumerate() {
+                            let qx = x + (kx as i64 - 2) * step;
+                            let qy = y + (ky as i64 - 2) * step;
+                            if qx < 0 || qy < 0 || qx >= width || qy >= height {
+                                continue;
+                            }
+                            let q = (qy * width + qx) as usize;
+                            let f_q = features[q];
+
+                            let color = distance_squared(c_p, compress(image.pixels[q])) / (sigma_color * sigma_color);
+                            let normal = distance_squared(f_p.normal, f_q.normal) / (self.sigma_normal * self.sigma_normal);
+                            let albedo = distance_squared(f_p.albedo, f_q.albedo) / (self.sigma_albedo * self.sigma_albedo);
+                            let depth = self.depth_distance(f_p.depth, f_q.depth);
+                            let weight = hx * hy * (-(color + normal + albedo + depth)).exp();
+
+                            sum += image.pixels[q] * weight;
+                            total += weight;
+                        }
+                    }
+                    // The center tap always has weight > 0.
+                    sum / total
+                })
+            })
+            .collect();
+        Framebuffer { width: image.width, height: image.height, pixels }
+    }
+
+    /// Relative depth difference; pixels that both missed everything match.
+    fn depth_distance(&self, a: f64, b: f64) -> f64 {
+        match (a.is_finite(), b.is_finite()) {
+            (true, true) => (a - b).abs() / (self.sigma_depth * a.max(b).max(1e-6)),
+            (false, false) => 0.0,
+            _ => f64::INFINITY,
+        }
+    }
+}
+
+fn distance_squared(a: Color, b: Color) -> f64 {
+    (a - b).length_squared()
+}
+
+fn compress(c: Color) -> Color {
+    Color::new(c.x / (1.0 + c.x), c.y / (1.0 + c.y), c.z / (1.0 + c.z))
+}
+
+fn demodulate(c: Color, albedo: Color) -> Color {
+    let div = |x: f64, a: f64| if a < MIN_ALBEDO { x } else { x / a };
+    Color::new(div(c.x, albedo.x), div(c.y, albedo.y), div(c.z, albedo.z))
+}
+
+fn remodulate(c: Color, albedo: Color) -> Color {
+    let mul = |x: f64, a: f64| if a < MIN_ALBEDO { x } else { x * a };
+    Color::new(mul(c.x, albedo.x), mul(c.y, albedo.y), mul(c.z, albedo.z))
+}
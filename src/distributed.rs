@@ -0,0 +1,597 @@
+//! Rendering one frame on several machines.
+//!
+//! A [`Coordinator`] listens for workers, sends each one the scene source and
+//! render settings, then hands out tiles. Workers ([`run_worker`]) render
+//! them with the usual [`Renderer`] and stream the float film tiles and pixel
+//! statistics back. A worker keeps one tile in flight per thread; the tiles
+//! of a worker that disconnects, or goes quiet for longer than the tile
+//! timeout, go back in the queue for the others. The
+//! coordinator merges tiles in tile order, so the image is identical to a
+//! local render with the same settings.
+//!
+//! Messages are little-endian. Relative file references in the scene, such
+//! as meshes and textures, are resolved against the coordinator's scene
+//! directory, so remote workers need the same files at the same paths.
+
+use crate::background::Background;
+use crate::checkpoint::{read_f64, read_u32, read_u64};
+use crate::film::FilmTile;
+use crate::filter::Filter;
+use crate::render::{PixelStats, RenderSettings, RenderState, Renderer};
+use crate::scene::{Scene, SceneError};
+use crate::vec3::Color;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Sent by workers on connecting, before their thread count.
+const MAGIC: &[u8; 8] = b"RTFARM01";
+
+const TAG_DONE: u8 = 0;
+const TAG_TILE: u8 = 1;
+
+/// Upper bound on the tiles one worker may have in flight.
+const MAX_IN_FLIGHT: u32 = 256;
+
+/// How often the coordinator checks for new workers.
+const ACCEPT_POLL: Duration = Duration::from_millis(20);
+
+/// How long a new connection has to identify itself as a worker.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default for [`Coordinator::with_tile_timeout`].
+pub const DEFAULT_TILE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Everything a worker needs to render tiles of a frame.
+#[derive(Clone, Debug)]
+pub struct Job {
+    /// Scene-file source text.
+    pub source: String,
+    /// Directory that relative file references in `source` are resolved against.
+    pub base_dir: PathBuf,
+    /// Replaces the scene's background, like `--background`.
+    pub background: Option<Background>,
+    /// Progressive passes and the time limit are not supported and ignored.
+    pub settings: RenderSettings,
+}
+
+impl Job {
+    /// Builds the job's scene.
+    pub fn scene(&self) -> Result<Scene, SceneError> {
+        let mut scene = Scene::parse_in(&self.source, &self.base_dir)?;
+        if let Some(background) = self.background {
+            scene.background = background;
+        }
+        Ok(scene)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let s = &self.settings;
+        let mut buf = Vec::new();
+        for v in [s.width, s.height, s.samples_per_pixel, s.max_depth, s.min_samples] {
+            buf.extend(v.to_le_bytes());
+        }
+        buf.extend(s.adaptive_threshold.unwrap_or(f64::NAN).to_le_bytes());
+        buf.extend(s.seed.to_le_bytes());
+        put_str(&mut buf, s.sampler.name());
+        put_str(&mut buf, s.filter.kind.name());
+        buf.extend(s.filter.radius.to_le_bytes());
+        match s.crop {
+            Some(rect) => {
+                buf.push(1);
+                rect.iter().for_each(|v| buf.extend(v.to_le_bytes()));
+            }
+            None => buf.push(0),
+        }
+        match self.background {
+            None => buf.push(0),
+            Some(Background::Gradient { horizon, zenith }) => {
+                buf.push(1);
+                put_color(&mut buf, horizon);
+                put_color(&mut buf, zenith);
+            }
+            Some(Background::Solid(color)) => {
+                buf.push(2);
+                put_color(&mut buf, color);
+            }
+        }
+        put_str(&mut buf, &self.base_dir.to_string_lossy());
+        put_str(&mut buf, &self.source);
+        buf
+    }
+
+    fn decode(r: &mut impl Read) -> io::Result<Self> {
+        let [width, height, samples_per_pixel, max_depth, min_samples] =
+            [read_u32(r)?, read_u32(r)?, read_u32(r)?, read_u32(r)?, read_u32(r)?];
+        let adaptive_threshold = Some(read_f64(r)?).filter(|t| !t.is_nan());
+        let seed = read_u64(r)?;
+        let sampler = read_str(r)?.parse().map_err(invalid)?;
+        let filter = Filter::with_radius(read_str(r)?.parse().map_err(invalid)?, read_f64(r)?);
+        let crop = match read_u8(r)? {
+            0 => None,
+            _ => Some([read_u32(r)?, read_u32(r)?, read_u32(r)?, read_u32(r)?]),
+        };
+        let background = match read_u8(r)? {
+            0 => None,
+            1 => Some(Background::Gradient { horizon: read_color(r)?, zenith: read_color(r)? }),
+            2 => Some(Background::Solid(read_color(r)?)),
+            tag => return Err(invalid(format!("unknown background tag {}", tag))),
+        };
+        let base_dir = PathBuf::from(read_str(r)?);
+        let source = read_str(r)?;
+        let settings = RenderSettings {
+            width,
+            height,
+            samples_per_pixel,
+            max_depth,
+            adaptive_threshold,
+            min_samples,
+            seed,
+            sampler,
+            filter,
+            crop,
+            ..RenderSettings::default()
+        };
+        Ok(Self { source, base_dir, background, settings })
+    }
+}
+
+/// Hands out the tiles of a [`Job`] to workers connecting over TCP.
+pub struct Coordinator {
+    listener: TcpListener,
+    job: Job,
+    tile_timeout: Duration,
+}
+
+/// Tiles not yet handed out, and the number not yet rendered.
+struct Queue {
+    pending: VecDeque<usize>,
+    remaining: usize,
+    /// Set when the render is abandoned; connections waiting for tiles give up.
+    aborted: bool,
+}
+
+/// State the coordinator's connection threads share.
+struct Shared<'a> {
+    job: &'a [u8],
+    tiles: &'a [[u32; 4]],
+    settings: &'a RenderSettings,
+    tile_timeout: Duration,
+    queue: Mutex<Queue>,
+    /// Signalled when tiles are requeued, the last one comes in or the render is aborted.
+    ready: Condvar,
+    /// Open connections by number, so the coordinator can unblock their
+    /// reads when it is done. `None` once it is, refusing new connections.
+    connections: Mutex<Option<BTreeMap<usize, TcpStream>>>,
+}
+
+type TileResult = (usize, FilmTile, Vec<PixelStats>);
+
+impl Coordinator {
+    pub fn bind(addr: impl ToSocketAddrs, job: Job) -> io::Result<Self> {
+        Ok(Self { listener: TcpListener::bind(addr)?, job, tile_timeout: DEFAULT_TILE_TIMEOUT })
+    }
+
+    /// Gives up on a worker, requeueing its tiles, when it neither returns
+    /// a tile nor accepts a request for this long. Should comfortably exceed
+    /// the time one tile takes.
+    pub fn with_tile_timeout(self, tile_timeout: Duration) -> Self {
+        Self { tile_timeout, ..self }
+    }
+
+    /// The address workers connect to; useful after binding port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Renders the job with whichever workers connect, calling `on_tile` as
+    /// each tile is merged. Returns once every tile is in, waiting for new
+    /// workers for as long as tiles remain.
+    pub fn run(self, on_tile: impl FnMut()) -> io::Result<RenderState> {
+        self.run_while(on_tile, || Ok(()))
+    }
+
+    /// Like [`Coordinator::run`], calling `check` whenever no tile has come
+    /// in for a moment and giving up with its error, e.g. once no workers
+    /// are left to wait for.
+    pub fn run_while(self, mut on_tile: impl FnMut(), mut check: impl FnMut() -> io::Result<()>) -> io::Result<RenderState> {
+        let settings = &self.job.settings;
+        let tiles = Renderer::new(settings.clone()).tiles();
+        let job = self.job.encode();
+        let shared = Shared {
+            job: &job,
+            tiles: &tiles,
+            settings,
+            tile_timeout: self.tile_timeout,
+            queue: Mutex::new(Queue { pending: (0..tiles.len()).collect(), remaining: tiles.len(), aborted: false }),
+            ready: Condvar::new(),
+            connections: Mutex::new(Some(BTreeMap::new())),
+        };
+        let mut state = RenderState::new(settings);
+        let finished = AtomicBool::new(false);
+        let (results, received) = mpsc::channel::<TileResult>();
+        self.listener.set_nonblocking(true)?;
+
+        let outcome = thread::scope(|scope| {
+            let (shared, finished, results) = (&shared, &finished, &results);
+            scope.spawn(move || {
+                let mut id = 0;
+                while !finished.load(Ordering::Relaxed) {
+                    match self.listener.accept() {
+                        Ok((stream, _)) => {
+                            let results = results.clone();
+                            id += 1;
+                            scope.spawn(move || serve(id, stream, shared, results));
+                        }
+                        Err(_) => thread::sleep(ACCEPT_POLL),
+                    }
+                }
+            });
+
+            // Tiles arrive in any order; merge them in tile order.
+            let mut arrived = BTreeMap::new();
+            let mut next = 0;
+            let mut outcome = Ok(());
+            while next < tiles.len() {
+                let (index, tile, stats) = match received.recv_timeout(ACCEPT_POLL) {
+                    Ok(result) => result,
+                    Err(_) => match check() {
+                        Ok(()) => continue,
+                        Err(e) => {
+                            outcome = Err(e);
+                            break;
+                        }
+                    },
+                };
+                arrived.insert(index, (tile, stats));
+                while let Some((tile, stats)) = arrived.remove(&next) {
+                    state.merge_tile(tiles[next], &tile, stats);
+                    on_tile();
+                    next += 1;
+                }
+            }
+            finished.store(true, Ordering::Relaxed);
+
+            // Connections that finished the handshake get `TAG_DONE` or, when
+            // aborting, wake up to no tiles. Whatever is still blocked reading,
+            // like a connection that never identified itself or a worker that
+            // vanished without closing it, is cut off rather than waited for.
+            let aborted = outcome.is_err();
+            if aborted {
+                shared.queue.lock().unwrap().aborted = true;
+                shared.ready.notify_all();
+            }
+            let connections = shared.connections.lock().unwrap().take().unwrap_or_default();
+            for stream in connections.values() {
+                let _ = stream.shutdown(if aborted { Shutdown::Both } else { Shutdown::Read });
+            }
+            outcome
+        });
+        outcome.map(|()| state)
+    }
+}
+
+/// Talks to one worker until the job is done or the connection fails or
+/// times out, in which case its unfinished tiles are requeued.
+fn serve(id: usize, stream: TcpStream, shared: &Shared, results: Sender<TileResult>) {
+    {
+        let mut connections = shared.connections.lock().unwrap();
+        let (Some(connections), Ok(clone)) = (connections.as_mut(), stream.try_clone()) else {
+            return;
+        };
+        connections.insert(id, clone);
+    }
+    let mut in_flight = Vec::new();
+    if serve_tiles(&stream, shared, &results, &mut in_flight).is_err() && !in_flight.is_empty() {
+        shared.queue.lock().unwrap().pending.extend(in_flight);
+        shared.ready.notify_all();
+    }
+    if let Some(connections) = shared.connections.lock().unwrap().as_mut() {
+        connections.remove(&id);
+    }
+}
+
+fn serve_tiles(stream: &TcpStream, shared: &Shared, results: &Sender<TileResult>, in_flight: &mut Vec<usize>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut writer = stream;
+
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a ray-tracer worker"));
+    }
+    let capacity = read_u32(&mut reader)?.clamp(1, MAX_IN_FLIGHT) as usize;
+    stream.set_read_timeout(Some(shared.tile_timeout))?;
+    stream.set_write_timeout(Some(shared.tile_timeout))?;
+    writer.write_all(shared.job)?;
+
+    loop {
+        // Top the worker up to its capacity. With nothing in flight and nothing
+        // to hand out, wait: tiles of a failing worker may still come back.
+        let mut handed_out = Vec::new();
+        {
+            let mut queue = shared.queue.lock().unwrap();
+            while in_flight.is_empty() && queue.pending.is_empty() && queue.remaining > 0 && !queue.aborted {
+                queue = shared.ready.wait(queue).unwrap();
+            }
+            if queue.aborted {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "render aborted"));
+            }
+            if in_flight.is_empty() && queue.remaining == 0 {
+                drop(queue);
+                return writer.write_all(&[TAG_DONE]);
+            }
+            while in_flight.len() + handed_out.len() < capacity {
+                let Some(index) = queue.pending.pop_front() else { break };
+                handed_out.push(index);
+            }
+        }
+        // Track every tile before writing any, so a failed write requeues them all.
+        in_flight.extend(&handed_out);
+        for index in handed_out {
+            let mut request = vec![TAG_TILE];
+            request.extend((index as u32).to_le_bytes());
+            shared.tiles[index].iter().for_each(|v| request.extend(v.to_le_bytes()));
+            writer.write_all(&request)?;
+        }
+
+        let (index, tile, stats) = read_result(&mut reader, shared)?;
+        let Some(position) = in_flight.iter().position(|&i| i == index) else {
+            return Err(invalid(format!("worker returned tile {} which it was not given", index)));
+        };
+        in_flight.swap_remove(position);
+        // Send before counting the tile as done, so once workers are told the
+        // job is done every tile is on its way to the merge. The receiver only
+        // goes away once every tile is in or the render is aborted.
+        let _ = results.send((index, tile, stats));
+        let mut queue = shared.queue.lock().unwrap();
+        queue.remaining -= 1;
+        if queue.remaining == 0 {
+            shared.ready.notify_all();
+        }
+    }
+}
+
+/// Reads one rendered tile, checking it against the tile it claims to be.
+fn read_result(r: &mut impl Read, shared: &Shared) -> io::Result<TileResult> {
+    let index = read_u32(r)? as usize;
+    let Some(&[x0, y0, x1, y1]) = shared.tiles.get(index) else {
+        return Err(invalid(format!("unknown tile {}", index)));
+    };
+    let [fx0, fy0, fx1, fy1] = [read_u32(r)?, read_u32(r)?, read_u32(r)?, read_u32(r)?];
+    if fx0 > x0 || fy0 > y0 || fx1 < x1 || fy1 < y1 || fx1 > shared.settings.width || fy1 > shared.settings.height {
+        return Err(invalid(format!("film tile for tile {} has the wrong extent", index)));
+    }
+
+    let n = ((fx1 - fx0) * (fy1 - fy0)) as usize;
+    let mut sums = Vec::with_capacity(n);
+    let mut weights = Vec::with_capacity(n);
+    for _ in 0..n {
+        sums.push(read_color(r)?);
+        weights.push(read_f64(r)?);
+    }
+    let mut stats = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+    for _ in 0..stats.capacity() {
+        stats.push(PixelStats { count: read_u32(r)?, mean: read_f64(r)?, m2: read_f64(r)? });
+    }
+    let tile = FilmTile { filter: shared.settings.filter, x0: fx0, y0: fy0, x1: fx1, y1: fy1, sums, weights };
+    Ok((index, tile, stats))
+}
+
+/// Connects to the coordinator at `addr` and renders the tiles it hands out,
+/// one per rayon thread at a time, until the job is done.
+pub fn run_worker(addr: impl ToSocketAddrs) -> io::Result<()> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+    let mut writer = &stream;
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(rayon::current_num_threads() as u32).to_le_bytes())?;
+
+    let mut reader = BufReader::new(&stream);
+    let job = Job::decode(&mut reader)?;
+    let scene = job.scene().map_err(invalid)?;
+    let renderer = Renderer::new(job.settings);
+    let settings = &renderer.settings;
+    let [rx0, ry0, rx1, ry1] = settings.sampled_region();
+    let state = RenderState::new(settings);
+    let cam = scene.camera.build(settings.aspect_ratio());
+
+    let writer = Mutex::new(writer);
+    let failed = Mutex::new(None);
+    let (renderer, scene, state, cam, writer, failed) = (&renderer, &scene, &state, &cam, &writer, &failed);
+    rayon::in_place_scope(|s| -> io::Result<()> {
+        loop {
+            match read_u8(&mut reader)? {
+                TAG_DONE => return Ok(()),
+                TAG_TILE => {
+                    let index = read_u32(&mut reader)?;
+                    let rect = [read_u32(&mut reader)?, read_u32(&mut reader)?, read_u32(&mut reader)?, read_u32(&mut reader)?];
+                    let [x0, y0, x1, y1] = rect;
+                    if x0 >= x1 || y0 >= y1 || x0 < rx0 || y0 < ry0 || x1 > rx1 || y1 > ry1 {
+                        return Err(invalid(format!("tile {:?} lies outside the image", rect)));
+                    }
+                    s.spawn(move |_| {
+                        let (tile, stats) = renderer.render_tile(scene, cam, state, rect, settings.samples_per_pixel);
+                        let message = encode_result(index, &tile, &stats);
+                        if let Err(e) = writer.lock().unwrap().write_all(&message) {
+                            *failed.lock().unwrap() = Some(e);
+                        }
+                    });
+                }
+                tag => return Err(invalid(format!("unknown message tag {}", tag))),
+            }
+        }
+    })?;
+    match failed.lock().unwrap().take() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn encode_result(index: u32, tile: &FilmTile, stats: &[PixelStats]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(20 + tile.sums.len() * 32 + stats.len() * 20);
+    buf.extend(index.to_le_bytes());
+    for v in [tile.x0, tile.y0, tile.x1, tile.y1] {
+        buf.extend(v.to_le_bytes());
+    }
+    for (&sum, &weight) in tile.sums.iter().zip(&tile.weights) {
+        put_color(&mut buf, sum);
+        buf.extend(weight.to_le_bytes());
+    }
+    for pixel in stats {
+        buf.extend(pixel.count.to_le_bytes());
+        buf.extend(pixel.mean.to_le_bytes());
+        buf.extend(pixel.m2.to_le_bytes());
+    }
+    buf
+}
+
+fn invalid(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend((s.len() as u32).to_le_bytes());
+    buf.extend(s.as_bytes());
+}
+
+fn put_color(buf: &mut Vec<u8>, c: Color) {
+    for v in [c.x, c.y, c.z] {
+        buf.extend(v.to_le_bytes());
+    }
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_str(r: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u32(r)? as usize];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(invalid)
+}
+
+fn read_color(r: &mut impl Read) -> io::Result<Color> {
+    Ok(Color::new(read_f64(r)?, read_f64(r)?, read_f64(r)?))
+}
+
+/// Directory to send as [`Job::base_dir`] for a scene file at `path`: its
+/// parent, made absolute so workers don't depend on their working directory.
+pub fn scene_base_dir(path: impl AsRef<Path>) -> PathBuf {
+    let parent = path.as_ref().parent().unwrap_or(Path::new(""));
+    std::fs::canonicalize(if parent.as_os_str().is_empty() { Path::new(".") } else { parent }).unwrap_or(parent.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterKind;
+    use crate::scene::DEFAULT_SCENE;
+
+    fn job() -> Job {
+        let settings = RenderSettings {
+            width: 48,
+            height: 40,
+            samples_per_pixel: 2,
+            max_depth: 4,
+            filter: Filter::new(FilterKind::Gaussian),
+            ..RenderSettings::default()
+        };
+        Job { source: DEFAULT_SCENE.to_string(), base_dir: PathBuf::new(), background: None, settings }
+    }
+
+    fn local_render(job: &Job) -> Vec<Color> {
+        Renderer::new(job.settings.clone()).render(&job.scene().unwrap()).pixels
+    }
+
+    #[test]
+    fn workers_on_localhost_match_a_local_render() {
+        let job = job();
+        let coordinator = Coordinator::bind("127.0.0.1:0", job.clone()).unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let workers: Vec<_> = (0..3).map(|_| thread::spawn(move || run_worker(addr))).collect();
+
+        let state = coordinator.run(|| {}).unwrap();
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+        assert_eq!(state.output().image.pixels, local_render(&job));
+    }
+
+    #[test]
+    fn tiles_of_a_disconnected_worker_are_reassigned() {
+        let job = job();
+        let coordinator = Coordinator::bind("127.0.0.1:0", job.clone()).unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let workers = thread::spawn(move || {
+            // Take a few tiles, then hang up without rendering them.
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(MAGIC).unwrap();
+            stream.write_all(&4u32.to_le_bytes()).unwrap();
+            Job::decode(&mut stream).unwrap();
+            assert_eq!(read_u8(&mut stream).unwrap(), TAG_TILE);
+            drop(stream);
+            run_worker(addr)
+        });
+
+        let state = coordinator.run(|| {}).unwrap();
+        workers.join().unwrap().unwrap();
+        assert_eq!(state.output().image.pixels, local_render(&job));
+    }
+
+    #[test]
+    fn a_connection_that_never_identifies_itself_does_not_hold_up_the_render() {
+        let job = job();
+        let coordinator = Coordinator::bind("127.0.0.1:0", job.clone()).unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let probe = TcpStream::connect(addr).unwrap();
+        let worker = thread::spawn(move || run_worker(addr));
+
+        let state = coordinator.run(|| {}).unwrap();
+        worker.join().unwrap().unwrap();
+        assert_eq!(state.output().image.pixels, local_render(&job));
+        drop(probe);
+    }
+
+    #[test]
+    fn tiles_of_a_stalled_worker_are_reassigned() {
+        let job = job();
+        let coordinator = Coordinator::bind("127.0.0.1:0", job.clone()).unwrap().with_tile_timeout(Duration::from_millis(200));
+        let addr = coordinator.local_addr().unwrap();
+        let stalled = thread::spawn(move || {
+            // Take tiles and sit on them with the connection open, until the
+            // coordinator gives up and closes it.
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(MAGIC).unwrap();
+            stream.write_all(&4u32.to_le_bytes()).unwrap();
+            Job::decode(&mut stream).unwrap();
+            assert_eq!(read_u8(&mut stream).unwrap(), TAG_TILE);
+            while read_u8(&mut stream).is_ok() {}
+        });
+        let worker = thread::spawn(move || run_worker(addr));
+
+        let state = coordinator.run(|| {}).unwrap();
+        worker.join().unwrap().unwrap();
+        stalled.join().unwrap();
+        assert_eq!(state.output().image.pixels, local_render(&job));
+    }
+
+    #[test]
+    fn run_while_gives_up_when_the_check_fails() {
+        let coordinator = Coordinator::bind("127.0.0.1:0", job()).unwrap();
+        let result = coordinator.run_while(|| {}, || Err(io::Error::other("no workers left")));
+        assert_eq!(result.err().unwrap().to_string(), "no workers left");
+    }
+}
@@ -0,0 +1,104 @@
+//! Sample accumulation with reconstruction filtering.
+//!
+//! Samples are splatted into every pixel whose center lies within the filter
+//! radius, weighted by the filter; a pixel's value is its weighted sum divided
+//! by the sum of weights. Threads render into private [`FilmTile`]s which are
+//! merged back into the [`Film`] in a fixed order, so results don't depend on
+//! scheduling.
+
+use crate::filter::Filter;
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Color;
+
+/// Weighted sample sums for the whole image, row-major from the top-left corner.
+pub struct Film {
+    pub width: u32,
+    pub height: u32,
+    pub filter: Filter,
+    pub(crate) sums: Vec<Color>,
+    pub(crate) weights: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: Filter) -> Self {
+        let n = width as usize * height as usize;
+        Self { width, height, filter, sums: vec![Color::zero(); n], weights: vec![0.0; n] }
+    }
+
+    /// A tile for samples taken inside pixels `x0..x1` × `y0..y1`. It also
+    /// covers the neighbouring pixels those samples reach through the filter,
+    /// as far as they lie in the image.
+    pub fn tile(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> FilmTile {
+        let margin = self.filter.radius.ceil() as u32;
+        let x0 = x0.saturating_sub(margin);
+        let y0 = y0.saturating_sub(margin);
+        let x1 = (x1 + margin).min(self.width);
+        let y1 = (y1 + margin).min(self.height);
+        let n = ((x1 - x0) * (y1 - y0)) as usize;
+        FilmTile { filter: self.filter, x0, y0, x1, y1, sums: vec![Color::zero(); n], weights: vec![0.0; n] }
+    }
+
+    /// Adds the contents of `tile`.
+    pub fn merge(&mut self, tile: &FilmTile) {
+        let tile_width = (tile.x1 - tile.x0) as usize;
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let src = (y - tile.y0) as usize * tile_width + (x - tile.x0) as usize;
+                let dst = (y * self.width + x) as usize;
+                self.sums[dst] += tile.sums[src];
+                self.weights[dst] += tile.weights[src];
+            }
+        }
+    }
+
+    /// The reconstructed image; pixels without any weight are black.
+    pub fn image(&self) -> Framebuffer {
+        let mut image = Framebuffer::new(self.width, self.height);
+        for ((pixel, &sum), &weight) in image.pixels.iter_mut().zip(&self.sums).zip(&self.weights) {
+            if weight != 0.0 {
+                *pixel = sum / weight;
+            }
+        }
+        image
+    }
+}
+
+/// A rectangle of the film (`x0..x1` × `y0..y1`) owned by one thread.
+pub struct FilmTile {
+    pub(crate) filter: Filter,
+    pub(crate) x0: u32,
+    pub(crate) y0: u32,
+    pub(crate) x1: u32,
+    pub(crate) y1: u32,
+    pub(crate) sums: Vec<Color>,
+    pub(crate) weights: Vec<f64>,
+}
+
+impl FilmTile {
+    /// Splats a sample at continuous image position (`x`, `y`), in pixels from
+    /// the top-left corner; pixel (i, j) spans [i, i + 1) × [j, j + 1).
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color) {
+        let r = self.filter.radius;
+        // Pixels whose centers lie strictly within the radius.
+        let first = |c: f64, lo: u32| ((c - r - 0.5).floor() + 1.0).max(lo as f64) as u32;
+        let last = |c: f64, hi: u32| ((c + r - 0.5).ceil() - 1.0).min(hi as f64 - 1.0);
+        let (px0, py0) = (first(x, self.x0), first(y, self.y0));
+        let (px1, py1) = (last(x, self.x1), last(y, self.y1));
+        if px1 < px0 as f64 || py1 < py0 as f64 {
+            return;
+        }
+
+        let tile_width = (self.x1 - self.x0) as usize;
+        for py in py0..=py1 as u32 {
+            for px in px0..=px1 as u32 {
+                let weight = self.filter.evaluate(x - (px as f64 + 0.5), y - (py as f64 + 0.5));
+                if weight == 0.0 {
+                    continue;
+                }
+                let i = (py - self.y0) as usize * tile_width + (px - self.x0) as usize;
+                self.sums[i] += color * weight;
+                self.weights[i] += weight;
+            }
+        }
+    }
+}
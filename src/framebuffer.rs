@@ -0,0 +1,106 @@
+use crate::tonemap::DisplayTransform;
+use crate::vec3::Color;
+use image::codecs::hdr::HdrEncoder;
+use image::{Rgb, RgbImage};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Floating-point image of linear radiance, stored row-major from the top-left corner.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>,
+}
+
+/// Output encodings, chosen from the file extension.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// OpenEXR, 32-bit float RGB.
+    Exr,
+    /// Radiance RGBE (`.hdr`).
+    Hdr,
+    /// Portable float map, 32-bit float RGB.
+    Pfm,
+    /// Any 8-bit format the `image` crate can write (PNG, JPEG, ...).
+    Ldr,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let ext = path.as_ref().extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        match ext.as_str() {
+            "exr" => OutputFormat::Exr,
+            "hdr" => OutputFormat::Hdr,
+            "pfm" => OutputFormat::Pfm,
+            _ => OutputFormat::Ldr,
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, pixels: vec![Color::zero(); width as usize * height as usize] }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    /// The pixels `x0..x1` × `y0..y1` as an image of their own.
+    pub fn crop(&self, [x0, y0, x1, y1]: [u32; 4]) -> Framebuffer {
+        let mut cropped = Framebuffer::new(x1 - x0, y1 - y0);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                cropped.set(x - x0, y - y0, self.get(x, y));
+            }
+        }
+        cropped
+    }
+
+    /// Display-encoded 8-bit copy of the image.
+    pub fn to_rgb8(&self, display: &DisplayTransform) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| Rgb(display.encode(self.get(x, y))))
+    }
+
+    /// Writes the image in the format implied by the extension of `path`:
+    /// untouched linear radiance for `.exr`, `.hdr` and `.pfm`, otherwise 8-bit
+    /// sRGB passed through `display`.
+    pub fn save(&self, path: impl AsRef<Path>, display: &DisplayTransform) -> image::ImageResult<()> {
+        let path = path.as_ref();
+        match OutputFormat::from_path(path) {
+            OutputFormat::Exr => {
+                let data = self.pixels.iter().flat_map(|c| [c.x as f32, c.y as f32, c.z as f32]).collect();
+                let img = image::Rgb32FImage::from_raw(self.width, self.height, data).expect("framebuffer size matches");
+                img.save(path)
+            }
+            OutputFormat::Hdr => {
+                let data: Vec<Rgb<f32>> = self.pixels.iter().map(|c| Rgb([c.x as f32, c.y as f32, c.z as f32])).collect();
+                let writer = BufWriter::new(File::create(path)?);
+                HdrEncoder::new(writer).encode(&data, self.width as usize, self.height as usize)
+            }
+            OutputFormat::Pfm => Ok(self.write_pfm(path)?),
+            OutputFormat::Ldr => self.to_rgb8(display).save(path),
+        }
+    }
+
+    /// PFM stores rows bottom-to-top; a negative scale marks little-endian data.
+    fn write_pfm(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let c = self.get(x, y);
+                for channel in [c.x, c.y, c.z] {
+                    out.write_all(&(channel as f32).to_le_bytes())?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
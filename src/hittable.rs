@@ -1,4 +1,6 @@
+use crate::aabb::Aabb;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::{Point3, Vec3};
 use std::sync::Arc;
 
@@ -9,22 +11,43 @@ pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
     pub t: f64,
+    /// Surface coordinates of the hit point.
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
     pub mat: Arc<dyn Material + Send + Sync>,
+    /// IDs for the object/material AOV passes; 0 unless set by [`Tagged`].
+    pub object_id: u32,
+    pub material_id: u32,
 }
 
 impl HitRecord {
-    pub fn new(p: Point3, outward_normal: Vec3, t: f64, r: &Ray, mat: Arc<dyn Material + Send + Sync>) -> Self {
+    pub fn new(p: Point3, outward_normal: Vec3, t: f64, u: f64, v: f64, r: &Ray, mat: Arc<dyn Material + Send + Sync>) -> Self {
         let front_face = r.direction.dot(&outward_normal) < 0.0;
         let normal = if front_face { outward_normal } else { -outward_normal };
-        Self { p, normal, t, front_face, mat }
+        Self { p, normal, t, u, v, front_face, mat, object_id: 0, material_id: 0 }
     }
 }
 
 pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    /// Box enclosing the object, or `None` if it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Solid-angle density with which `random(origin)` returns `direction`.
+    /// Zero for objects that cannot be sampled as lights.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// A direction from `origin` towards a random point on the object.
+    fn random(&self, _origin: &Point3, _sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
 }
 
+#[derive(Clone, Default)]
 pub struct HittableList {
     pub objects: Vec<Arc<dyn Hittable>>,
 }
@@ -53,4 +76,64 @@ impl Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut objects = self.objects.iter();
+        let first = objects.next()?.bounding_box()?;
+        objects.try_fold(first, |acc, obj| Some(acc.surrounding(&obj.bounding_box()?)))
+    }
+
+    /// Objects are picked uniformly, so the density is the mean of theirs.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self.objects.iter().map(|obj| obj.pdf_value(origin, direction)).sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let index = ((sampler.get_1d() * self.objects.len() as f64) as usize).min(self.objects.len() - 1);
+        self.objects[index].random(origin, sampler)
+    }
+}
+
+/// Stamps object and material IDs onto every hit of the wrapped object. A
+/// zero ID leaves the one set further down in place, e.g. by the objects of
+/// an instanced prototype.
+pub struct Tagged {
+    pub object: Arc<dyn Hittable>,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl Tagged {
+    pub fn new(object: Arc<dyn Hittable>, object_id: u32, material_id: u32) -> Self {
+        Self { object, object_id, material_id }
+    }
+}
+
+impl Hittable for Tagged {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut rec = self.object.hit(r, t_min, t_max)?;
+        if self.object_id != 0 {
+            rec.object_id = self.object_id;
+        }
+        if self.material_id != 0 {
+            rec.material_id = self.material_id;
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        self.object.random(origin, sampler)
+    }
 }
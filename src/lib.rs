@@ -0,0 +1,61 @@
+//! A tiny path tracer.
+//!
+//! Build a [`Scene`] (from a scene file or by hand), then hand it to a
+//! [`Renderer`] to get a floating-point [`Framebuffer`] of linear radiance:
+//!
+//! ```no_run
+//! use ray_tracer::{DisplayTransform, RenderSettings, Renderer, Scene};
+//!
+//! let scene = Scene::load("scenes/cornell.toml").expect("valid scene");
+//! let settings = RenderSettings { width: 300, height: 300, ..RenderSettings::default() };
+//! let image = Renderer::new(settings).render(&scene);
+//! image.save("cornell.png", &DisplayTransform::default()).expect("writable output");
+//! ```
+
+pub mod aabb;
+pub mod accel;
+pub mod aov;
+pub mod background;
+pub mod camera;
+pub mod checkpoint;
+pub mod denoise;
+pub mod distributed;
+pub mod film;
+pub mod filter;
+pub mod framebuffer;
+pub mod hittable;
+pub mod material;
+pub mod obj;
+pub mod onb;
+pub mod perlin;
+pub mod planar;
+pub mod quadric;
+pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod scene;
+pub mod scene_parser;
+pub mod sphere;
+pub mod texture;
+pub mod tonemap;
+pub mod transform;
+pub mod triangle;
+pub mod vec3;
+
+pub use accel::{Blas, Instance, Tlas};
+pub use aov::{Aov, AovImage};
+pub use background::Background;
+pub use camera::Camera;
+pub use denoise::Denoiser;
+pub use film::{Film, FilmTile};
+pub use filter::{Filter, FilterKind};
+pub use framebuffer::{Framebuffer, OutputFormat};
+pub use hittable::{HitRecord, Hittable, HittableList};
+pub use material::Material;
+pub use ray::Ray;
+pub use render::{RenderOutput, RenderSettings, RenderState, Renderer};
+pub use sampler::{Sampler, SamplerKind};
+pub use scene::Scene;
+pub use tonemap::{DisplayTransform, ToneMapper};
+pub use transform::{Mat4, Transform, Transformed};
+pub use vec3::{Color, Point3, Vec3};
@@ -1,47 +1,160 @@
-mod vec3;
-mod ray;
-mod hittable;
-mod sphere;
-mod material;
-mod camera;
-
-use vec3::{Vec3, Color, Point3};
-use ray::Ray;
-use sphere::Sphere;
-use hittable::{Hittable, HittableList};
-use camera::Camera;
-use std::sync::Arc;
-use image::{RgbImage, Rgb};
+use ray_tracer::distributed::{self, Coordinator, Job};
+use ray_tracer::{aov, Aov, Background, Color, Denoiser, DisplayTransform, Filter, FilterKind, RenderSettings, RenderState, Renderer, SamplerKind, Scene, ToneMapper};
 use indicatif::{ProgressBar, ProgressStyle};
-use rayon::prelude::*;
-use material::{Lambertian, Metal, Dielectric};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use image::imageops::FilterType;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 /// Simple CLI for the ray tracer
 #[derive(Parser, Debug)]
 #[command(author, version, about = "A tiny ray tracer", long_about = None)]
 struct Cli {
-    /// Image width in pixels
-    #[arg(short, long, default_value_t = 400)]
-    width: u32,
+    #[command(subcommand)]
+    command: Option<Commands>,
 
-    /// Image height in pixels. If omitted, height is computed from aspect ratio
+    /// Scene file to render (TOML). Defaults to the built-in three-sphere scene
+    #[arg(long)]
+    scene: Option<String>,
+
+    /// Image width in pixels [default: scene setting or 400]
+    #[arg(short, long)]
+    width: Option<u32>,
+
+    /// Image height in pixels. If omitted, height is computed from a 16:9 aspect ratio
     #[arg(short, long)]
     height: Option<u32>,
 
-    /// Samples per pixel
-    #[arg(short = 's', long, default_value_t = 50)]
-    samples: u32,
+    /// Samples per pixel [default: scene setting or 50]
+    #[arg(short = 's', long)]
+    samples: Option<u32>,
+
+    /// Stop sampling a pixel once the relative standard error of its luminance
+    /// drops below this (e.g. 0.01); `--samples` becomes the per-pixel maximum
+    #[arg(long)]
+    adaptive_threshold: Option<f64>,
+
+    /// Samples every pixel takes before adaptive sampling may stop it
+    #[arg(long, default_value_t = 16)]
+    min_samples: u32,
 
-    /// Max recursion depth
-    #[arg(short = 'd', long, default_value_t = 10)]
-    max_depth: u32,
+    /// Write an image of the samples spent per pixel (black = none, yellow = `--samples`)
+    #[arg(long)]
+    sample_heatmap: Option<String>,
+
+    /// Max recursion depth [default: scene setting or 10]
+    #[arg(short = 'd', long)]
+    max_depth: Option<u32>,
 
-    /// Output filename
+    /// Background for rays that miss everything: `sky` or a linear `r,g,b` color such as `0,0,0`
+    /// [default: scene setting or sky]
+    #[arg(long, value_parser = parse_background)]
+    background: Option<Background>,
+
+    /// Output filename. `.exr`, `.hdr` and `.pfm` keep linear floating-point
+    /// radiance; other extensions are written as display-encoded 8-bit images
     #[arg(short, long, default_value = "render.png")]
     output: String,
 
+    /// Tone mapping curve for 8-bit outputs: clamp, reinhard, extended-reinhard, aces or hable
+    #[arg(long, default_value_t = ToneMapper::Clamp)]
+    tonemap: ToneMapper,
+
+    /// Exposure adjustment in stops (EV) applied before tone mapping
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    exposure: f64,
+
+    /// Linear radiance mapped to white by extended-reinhard (default 4) and hable (default 11.2)
+    #[arg(long)]
+    white_point: Option<f64>,
+
+    /// Extra passes to write next to the image, comma-separated: depth, normal,
+    /// albedo, object-id, material-id, position, uv. `.exr` outputs store them as
+    /// channels of the same file, other formats as `<name>.<pass>.<ext>`
+    #[arg(long, value_delimiter = ',')]
+    aov: Vec<Aov>,
+
+    /// Denoise the render with an edge-avoiding filter guided by the albedo,
+    /// normal and depth passes
+    #[arg(long, default_value_t = false)]
+    denoise: bool,
+
+    /// Random seed. Renders with the same seed and settings are identical,
+    /// whatever the thread count
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Sample distribution: independent, stratified, halton, sobol or blue-noise
+    #[arg(long, default_value_t = SamplerKind::Independent)]
+    sampler: SamplerKind,
+
+    /// Pixel reconstruction filter: box, tent, gaussian, mitchell or lanczos
+    #[arg(long, default_value_t = FilterKind::Box)]
+    filter: FilterKind,
+
+    /// Filter radius in pixels [default: 0.5 box, 1 tent, 1.5 gaussian, 2 mitchell, 3 lanczos]
+    #[arg(long, value_parser = parse_radius)]
+    filter_radius: Option<f64>,
+
+    /// Render progressively: passes of `--pass-samples` samples over the whole
+    /// image until `--samples` is reached
+    #[arg(long, default_value_t = false)]
+    progressive: bool,
+
+    /// Samples per pixel added in each progressive pass
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    pass_samples: u32,
+
+    /// Render progressively for at most this long (e.g. `90`, `90s`, `5m`, `1h`),
+    /// then write the image so far. Without `--samples` there is no sample limit
+    #[arg(long, value_parser = parse_duration)]
+    time_limit: Option<Duration>,
+
+    /// In progressive mode, also write the image every this many passes, to
+    /// `<name>.pass<N>.<ext>`
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    snapshot_every: Option<u32>,
+
+    /// Render only the rectangle `x0,y0,x1,y1` of the full frame, in pixels from
+    /// the top-left corner or, if any value has a decimal point, as fractions of
+    /// the image size (e.g. `0.25,0.25,0.75,0.75`)
+    #[arg(long, value_parser = parse_crop)]
+    crop: Option<Crop>,
+
+    /// With `--crop`, write a full-size image that is black outside the crop
+    /// instead of just the cropped rectangle, e.g. for stitching crops together
+    #[arg(long, default_value_t = false, requires = "crop")]
+    crop_full: bool,
+
+    /// Periodically save the render in progress to this file so it can be resumed
+    #[arg(long)]
+    checkpoint: Option<String>,
+
+    /// Seconds between checkpoint writes
+    #[arg(long, default_value_t = 60)]
+    checkpoint_interval: u64,
+
+    /// Continue the render saved in `--checkpoint` instead of starting over. The
+    /// scene and settings must match; raising `--samples` refines the saved render
+    /// (except with `--sampler stratified`, whose strata depend on it)
+    #[arg(long, default_value_t = false, requires = "checkpoint")]
+    resume: bool,
+
+    /// Coordinate a distributed render: listen on this address (e.g. `0.0.0.0:7878`)
+    /// and hand out tiles to `ray-tracer worker --connect <host:port>` processes
+    #[arg(long, conflicts_with_all = ["progressive", "time_limit", "resume"])]
+    serve: Option<String>,
+
+    /// Start this many worker processes on this machine for a distributed render,
+    /// listening on a free localhost port unless `--serve` is given
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["progressive", "time_limit", "resume"])]
+    local_workers: u32,
+
+    /// Seconds a distributed render waits for a worker's next tile before giving
+    /// up on the worker and handing its tiles to others
+    #[arg(long, default_value_t = distributed::DEFAULT_TILE_TIMEOUT.as_secs(), value_parser = clap::value_parser!(u64).range(1..))]
+    tile_timeout: u64,
+
     /// Number of threads to use (optional)
     #[arg(long)]
     threads: Option<usize>,
@@ -60,38 +173,109 @@ struct Cli {
     set_pixel: Vec<String>,
 }
 
-fn ray_color(r: &Ray, world: &HittableList, depth: u32) -> Color {
-    if depth == 0 {
-        return Color::zero();
-    }
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Render tiles for a coordinator started with `--serve` or `--local-workers`
+    Worker {
+        /// Coordinator address, `host:port`
+        #[arg(long)]
+        connect: String,
+    },
+}
 
-    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
-        if let Some((atten, scattered)) = rec.mat.scatter(r, &rec) {
-            return atten * ray_color(&scattered, world, depth - 1);
+/// A `--crop` rectangle, in pixels or as fractions of the image size.
+#[derive(Copy, Clone, Debug)]
+enum Crop {
+    Pixels([u32; 4]),
+    Normalized([f64; 4]),
+}
+
+impl Crop {
+    /// The crop in pixels of a `width` × `height` image.
+    fn resolve(self, width: u32, height: u32) -> Result<[u32; 4], String> {
+        let [x0, y0, x1, y1] = match self {
+            Crop::Pixels(rect) => rect,
+            Crop::Normalized([x0, y0, x1, y1]) => {
+                let scale = |f: f64, size: u32| (f.clamp(0.0, 1.0) * size as f64).round() as u32;
+                [scale(x0, width), scale(y0, height), scale(x1, width), scale(y1, height)]
+            }
+        };
+        if x0 >= x1 || y0 >= y1 || x1 > width || y1 > height {
+            return Err(format!("crop {},{},{},{} is empty or outside the {}x{} image", x0, y0, x1, y1, width, height));
         }
-        return Color::zero();
+        Ok([x0, y0, x1, y1])
+    }
+}
+
+fn parse_crop(s: &str) -> Result<Crop, String> {
+    let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+    let invalid = || format!("expected x0,y0,x1,y1, got '{}'", s);
+    if parts.len() != 4 {
+        return Err(invalid());
+    }
+    if parts.iter().any(|p| p.contains('.')) {
+        let values: Vec<f64> = parts.iter().map(|p| p.parse()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+        Ok(Crop::Normalized([values[0], values[1], values[2], values[3]]))
+    } else {
+        let values: Vec<u32> = parts.iter().map(|p| p.parse()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+        Ok(Crop::Pixels([values[0], values[1], values[2], values[3]]))
+    }
+}
+
+fn parse_background(s: &str) -> Result<Background, String> {
+    if s == "sky" {
+        return Ok(Background::SKY);
+    }
+    let parts: Vec<f64> = s
+        .split(',')
+        .map(|p| p.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("expected 'sky' or r,g,b, got '{}'", s))?;
+    match parts[..] {
+        [r, g, b] => Ok(Background::Solid(Color::new(r, g, b))),
+        _ => Err(format!("expected 'sky' or r,g,b, got '{}'", s)),
+    }
+}
+
+fn parse_radius(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(r) if r > 0.0 => Ok(r),
+        _ => Err(format!("expected a positive number of pixels, got '{}'", s)),
+    }
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let scale = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(format!("unknown unit '{}' (expected s, m or h)", unit)),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(n) if n >= 0.0 => Ok(Duration::from_secs_f64(n * scale)),
+        _ => Err(format!("expected a duration such as 90s or 5m, got '{}'", s)),
     }
+}
 
-    let unit_direction = r.direction.unit_vector();
-    let t = 0.5 * (unit_direction.y + 1.0);
-    Color::new(1.0, 1.0, 1.0) * (1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
+/// Where snapshot `pass` of a progressive render to `path` is written.
+fn snapshot_path(path: &str, pass: u32) -> PathBuf {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("render");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.pass{:04}.{}", stem, pass, ext),
+        None => format!("{}.pass{:04}", stem, pass),
+    };
+    path.with_file_name(name)
 }
 
 fn main() {
     // Parse CLI
     let cli = Cli::parse();
 
-    // Image
-    let aspect_ratio = 16.0 / 9.0;
-    let image_width: u32 = cli.width;
-    let image_height: u32 = match cli.height {
-        Some(h) => h,
-        None => (image_width as f64 / aspect_ratio) as u32,
-    };
-    let samples_per_pixel = cli.samples;
-    let max_depth = cli.max_depth;
-    let output_file = cli.output;
-
     // Optional thread control
     if let Some(n) = cli.threads {
         rayon::ThreadPoolBuilder::new()
@@ -100,63 +284,234 @@ fn main() {
             .expect("Failed to build rayon thread pool");
     }
 
-    println!("Rendering {w}x{h}, {s} spp, max depth {d} -> {out}", w = image_width, h = image_height, s = samples_per_pixel, d = max_depth, out = output_file);
-
-    // World
-    let mut world = HittableList::new();
-
-    let mat_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
-    let mat_center = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
-    let mat_left = Arc::new(Dielectric::new(1.5));
-    let mat_right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0));
-
-    world.add(Arc::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, mat_ground)));
-    world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, mat_center)));
-    world.add(Arc::new(Sphere::new(Point3::new(-1.0, 0.0, -1.0), 0.5, mat_left.clone())));
-    world.add(Arc::new(Sphere::new(Point3::new(-1.0, 0.0, -1.0), -0.45, mat_left)));
-    world.add(Arc::new(Sphere::new(Point3::new(1.0, 0.0, -1.0), 0.5, mat_right)));
-
-    // Camera
-    let lookfrom = Point3::new(3.0, 3.0, 2.0);
-    let lookat = Point3::new(0.0, 0.0, -1.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = (lookfrom - lookat).length();
-    let aperture = 2.0;
-    let cam = Camera::new(lookfrom, lookat, vup, 20.0, aspect_ratio, aperture, dist_to_focus);
-
-    // Progress bar
-    let bar = ProgressBar::new(image_height as u64);
-    bar.set_style(ProgressStyle::default_bar().template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} rows").expect("progress template"));
-
-    // Render (rows in parallel)
-    let rows: Vec<Vec<[u8; 3]>> = (0..image_height).into_par_iter().map(|j| {
-        // Build row pixels for scanline `j` (bottom->top ordering)
-        let mut row_pixels: Vec<[u8; 3]> = Vec::with_capacity(image_width as usize);
-        for i in 0..image_width {
-            let mut pixel_color = Color::zero();
-            for _s in 0..samples_per_pixel {
-                let u = (i as f64 + rand::random::<f64>()) / (image_width as f64 - 1.0);
-                let v = (j as f64 + rand::random::<f64>()) / (image_height as f64 - 1.0);
-                let r = cam.get_ray(u, v);
-                pixel_color += ray_color(&r, &world, max_depth);
+    if let Some(Commands::Worker { connect }) = &cli.command {
+        if let Err(e) = distributed::run_worker(connect) {
+            eprintln!("Worker failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Scene
+    let mut scene = match &cli.scene {
+        Some(path) => match Scene::load(path) {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("Failed to load scene {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => Scene::builtin(),
+    };
+
+    // Image (command-line flags override the scene's [render] settings)
+    let defaults = RenderSettings::default();
+    let image_width: u32 = cli.width.or(scene.render.width).unwrap_or(defaults.width);
+    let image_height: u32 = match cli.height.or(scene.render.height) {
+        Some(h) => h,
+        None => (image_width as f64 / (16.0 / 9.0)) as u32,
+    };
+    // A time limit replaces the scene's sample count unless `--samples` is given
+    let samples_per_pixel = match (cli.samples, cli.time_limit) {
+        (Some(s), _) => s,
+        (None, Some(_)) => u32::MAX,
+        (None, None) => scene.render.samples.unwrap_or(defaults.samples_per_pixel),
+    };
+    let progressive = cli.progressive || cli.time_limit.is_some();
+    let max_depth = cli.max_depth.or(scene.render.max_depth).unwrap_or(defaults.max_depth);
+    let output_file = cli.output;
+
+    let crop = cli.crop.map(|crop| {
+        crop.resolve(image_width, image_height).unwrap_or_else(|e| {
+            eprintln!("Invalid --crop: {}", e);
+            std::process::exit(1);
+        })
+    });
+
+    let budget = match cli.time_limit {
+        Some(limit) if samples_per_pixel == u32::MAX => format!("for {:.0?}", limit),
+        Some(limit) => format!("{} spp or {:.0?}", samples_per_pixel, limit),
+        None => format!("{} spp", samples_per_pixel),
+    };
+    println!("Rendering {w}x{h}, {b}, max depth {d} -> {out}", w = image_width, h = image_height, b = budget, d = max_depth, out = output_file);
+
+    let settings = RenderSettings {
+        width: image_width,
+        height: image_height,
+        samples_per_pixel,
+        max_depth,
+        adaptive_threshold: cli.adaptive_threshold,
+        min_samples: cli.min_samples,
+        seed: cli.seed,
+        sampler: cli.sampler,
+        filter: match cli.filter_radius {
+            Some(radius) => Filter::with_radius(cli.filter, radius),
+            None => Filter::new(cli.filter),
+        },
+        pass_samples: progressive.then_some(cli.pass_samples),
+        crop,
+        time_limit: cli.time_limit,
+    };
+    if let Some(background) = cli.background {
+        scene.background = background;
+    }
+
+    let renderer = Renderer::new(settings);
+    let mut state = match &cli.checkpoint {
+        Some(path) if cli.resume => match RenderState::load(path, &renderer.settings, &scene) {
+            Ok(state) => {
+                println!("Resuming from {} ({:.1} samples per pixel so far)", path, state.output().average_samples());
+                state
+            }
+            Err(e) => {
+                eprintln!("Failed to resume from {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        _ => RenderState::new(&renderer.settings),
+    };
+
+    // Progress bar: tiles, or passes and the estimated noise when progressive
+    let bar = if !progressive {
+        let bar = ProgressBar::new(renderer.tile_count() as u64);
+        bar.set_style(ProgressStyle::default_bar().template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} tiles").expect("progress template"));
+        bar
+    } else if samples_per_pixel == u32::MAX {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(ProgressStyle::default_spinner().template("{spinner:.green} [{elapsed_precise}] {pos} passes, {msg}").expect("progress template"));
+        bar
+    } else {
+        let bar = ProgressBar::new(samples_per_pixel.div_ceil(cli.pass_samples) as u64);
+        bar.set_style(ProgressStyle::default_bar().template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} passes, {msg}").expect("progress template"));
+        bar
+    };
+    bar.enable_steady_tick(Duration::from_millis(250));
+    let display = DisplayTransform { tone_mapper: cli.tonemap, exposure: cli.exposure, white_point: cli.white_point };
+
+    // Render, checkpointing every `--checkpoint-interval` seconds and at the end
+    let save_checkpoint = |state: &RenderState| {
+        if let Some(path) = &cli.checkpoint
+            && let Err(e) = state.save(path, &renderer.settings, &scene)
+        {
+            eprintln!("Failed to write checkpoint {}: {}", path, e);
+        }
+    };
+    let interval = Duration::from_secs(cli.checkpoint_interval);
+    let mut last_checkpoint = Instant::now();
+    let mut last_pass = state.passes();
+    let on_tile = || {
+        if !progressive {
+            bar.inc(1);
+        }
+    };
+    if cli.serve.is_some() || cli.local_workers > 0 {
+        let (source, base_dir) = match &cli.scene {
+            Some(path) => (std::fs::read_to_string(path).expect("scene was readable"), distributed::scene_base_dir(path)),
+            None => (ray_tracer::scene::DEFAULT_SCENE.to_string(), PathBuf::new()),
+        };
+        let job = Job { source, base_dir, background: cli.background, settings: renderer.settings.clone() };
+        let addr = cli.serve.as_deref().unwrap_or("127.0.0.1:0");
+        let coordinator = Coordinator::bind(addr, job)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to listen on {}: {}", addr, e);
+                std::process::exit(1);
+            })
+            .with_tile_timeout(Duration::from_secs(cli.tile_timeout));
+        let addr = coordinator.local_addr().expect("bound listener has an address");
+        println!("Waiting for workers on {}", addr);
+
+        // Local workers split this machine's threads between them
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let threads = (cli.threads.unwrap_or(cores) / cli.local_workers.max(1) as usize).max(1);
+        let exe = std::env::current_exe().expect("path of the running executable");
+        let mut workers: Vec<_> = (0..cli.local_workers)
+            .map(|_| {
+                std::process::Command::new(&exe)
+                    .args(["--threads", &threads.to_string(), "worker", "--connect", &addr.to_string()])
+                    .spawn()
+                    .expect("Failed to start a local worker")
+            })
+            .collect();
+
+        // Without `--serve` nobody else can connect, so give up once every
+        // local worker has exited; they report their own errors on stderr.
+        let all_exited = || -> std::io::Result<()> {
+            if cli.serve.is_some() || workers.is_empty() {
+                return Ok(());
             }
-            row_pixels.push(pixel_color.to_rgb8(samples_per_pixel));
+            let mut statuses = Vec::new();
+            for worker in &mut workers {
+                match worker.try_wait()? {
+                    Some(status) => statuses.push(status.to_string()),
+                    None => return Ok(()),
+                }
+            }
+            statuses.dedup();
+            Err(std::io::Error::other(format!("every local worker exited before the render finished ({})", statuses.join(", "))))
+        };
+        state = coordinator.run_while(on_tile, all_exited).unwrap_or_else(|e| {
+            eprintln!("Distributed render failed: {}", e);
+            std::process::exit(1);
+        });
+        for mut worker in workers {
+            let _ = worker.wait();
         }
-        bar.inc(1);
-        row_pixels
-    }).collect();
+    } else {
+        renderer.render_into(&scene, &mut state, on_tile, |state| {
+            if progressive && state.passes() != last_pass {
+                last_pass = state.passes();
+                bar.set_position(last_pass as u64);
+                bar.set_message(format!("noise {:.2}%", state.noise_estimate() * 100.0));
+                if let Some(every) = cli.snapshot_every
+                    && last_pass % every == 0
+                {
+                    let path = snapshot_path(&output_file, last_pass);
+                    if let Err(e) = state.output().image.save(&path, &display) {
+                        eprintln!("Failed to write snapshot {}: {}", path.display(), e);
+                    }
+                }
+            }
+            if last_checkpoint.elapsed() >= interval {
+                save_checkpoint(state);
+                last_checkpoint = Instant::now();
+            }
+        });
+    }
+    save_checkpoint(&state);
+    let output = state.output();
 
-    // Assemble image
-    let mut imgbuf: RgbImage = RgbImage::new(image_width, image_height);
+    bar.finish();
 
-    for (row_idx, row) in rows.into_iter().enumerate() {
-        let y = image_height - 1 - row_idx as u32; // map back to image coords
-        for (x, px) in row.into_iter().enumerate() {
-            imgbuf.put_pixel(x as u32, y, Rgb(px));
+    if progressive {
+        println!("{} passes, {:.1} samples per pixel, estimated noise {:.2}%", state.passes(), output.average_samples(), state.noise_estimate() * 100.0);
+    } else if cli.adaptive_threshold.is_some() {
+        println!("Adaptive sampling: {:.1} samples per pixel on average", output.average_samples());
+    }
+    if let Some(path) = &cli.sample_heatmap {
+        let max_samples = samples_per_pixel.min(output.sample_counts.iter().copied().max().unwrap_or(0));
+        let mut heatmap = output.sample_heatmap(max_samples);
+        if let Some(rect) = crop.filter(|_| !cli.crop_full) {
+            heatmap = heatmap.crop(rect);
         }
+        heatmap.save(path, &DisplayTransform::default()).expect("Failed to save sample heatmap");
     }
+    let mut framebuffer = output.image;
 
-    bar.finish_with_message("done");
+    // The denoiser needs its guide passes even when they are not written out
+    let mut aovs = cli.aov.clone();
+    if cli.denoise {
+        for guide in [Aov::Albedo, Aov::Normal, Aov::Depth] {
+            if !aovs.contains(&guide) {
+                aovs.push(guide);
+            }
+        }
+    }
+    let mut passes = renderer.render_aovs(&scene, &aovs);
+    if cli.denoise {
+        let pass = |aov: Aov| &passes.iter().find(|p| p.aov == aov).expect("guide pass rendered").image;
+        framebuffer = Denoiser::default().denoise(&framebuffer, pass(Aov::Albedo), pass(Aov::Normal), pass(Aov::Depth));
+        passes.retain(|p| cli.aov.contains(&p.aov));
+    }
 
     // If an input image was provided, overlay or blend it into the final image
     if let Some(input_path) = &cli.input {
@@ -164,25 +519,14 @@ fn main() {
             Ok(img) => {
                 let img = img.to_rgb8();
                 let resized = image::imageops::resize(&img, image_width, image_height, FilterType::Lanczos3);
-                if cli.blend {
-                    for y in 0..image_height {
-                        for x in 0..image_width {
-                            let base = imgbuf.get_pixel(x, y).0;
-                            let src = resized.get_pixel(x, y).0;
-                            let blended = [
-                                ((base[0] as u16 + src[0] as u16) / 2) as u8,
-                                ((base[1] as u16 + src[1] as u16) / 2) as u8,
-                                ((base[2] as u16 + src[2] as u16) / 2) as u8,
-                            ];
-                            imgbuf.put_pixel(x, y, Rgb(blended));
-                        }
-                    }
-                } else {
-                    // Replace pixels with input image
-                    for y in 0..image_height {
-                        for x in 0..image_width {
-                            let src = resized.get_pixel(x, y);
-                            imgbuf.put_pixel(x, y, *src);
+                for y in 0..image_height {
+                    for x in 0..image_width {
+                        let src = Color::from_rgb8(resized.get_pixel(x, y).0);
+                        if cli.blend {
+                            framebuffer.set(x, y, (framebuffer.get(x, y) + src) * 0.5);
+                        } else {
+                            // Replace pixels with input image
+                            framebuffer.set(x, y, src);
                         }
                     }
                 }
@@ -202,7 +546,7 @@ fn main() {
         let parse_u8 = |s: &str| s.parse::<u8>().ok();
         if let (Some(x), Some(y), Some(r), Some(g), Some(b)) = (parse_u32(parts[0]), parse_u32(parts[1]), parse_u8(parts[2]), parse_u8(parts[3]), parse_u8(parts[4])) {
             if x < image_width && y < image_height {
-                imgbuf.put_pixel(x, y, Rgb([r, g, b]));
+                framebuffer.set(x, y, Color::from_rgb8([r, g, b]));
             } else {
                 eprintln!("Ignored --set-pixel '{}': coordinates out of bounds", spec);
             }
@@ -211,7 +555,15 @@ fn main() {
         }
     }
 
+    // Keep only the crop unless a full-size image was asked for
+    if let Some(rect) = crop.filter(|_| !cli.crop_full) {
+        framebuffer = framebuffer.crop(rect);
+        for pass in &mut passes {
+            pass.image = pass.image.crop(rect);
+        }
+    }
+
     // Save
-    imgbuf.save(&output_file).expect("Failed to save image");
-    println!("Wrote {out} ({width}x{height})", out = output_file, width = image_width, height = image_height);
+    aov::save(&output_file, &framebuffer, &passes, &display).expect("Failed to save image");
+    println!("Wrote {out} ({width}x{height})", out = output_file, width = framebuffer.width, height = framebuffer.height);
 }
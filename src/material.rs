@@ -1,58 +1,110 @@
 use crate::ray::Ray;
 use crate::vec3::{Color, Vec3};
 use crate::hittable::HitRecord;
-use rand::Rng;
+use crate::texture::{SolidColor, Texture};
+use crate::sampler::Sampler;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
 pub trait Material: Send + Sync {
-    /// Returns (attenuation, scattered ray) if scattering occurs.
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+    /// Returns (attenuation, scattered ray) if scattering occurs. All randomness comes from `sampler`.
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)>;
+
+    /// Solid-angle density with which `scatter` would have produced `scattered`.
+    /// Zero for (near-)specular materials, whose lobes light sampling cannot hit.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    /// BSDF times cosine for light arriving along `scattered` and leaving
+    /// along `r_in`. Only meaningful where `scattering_pdf` is non-zero.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Color {
+        Color::zero()
+    }
+
+    /// Surface color for the albedo AOV; black for materials without one.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::zero()
+    }
+
+    /// Radiance emitted from the hit point towards the incoming ray.
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::zero()
+    }
 }
 
 pub struct Lambertian {
-    pub albedo: Color,
+    pub albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let mut scatter_direction = rec.normal + Vec3::sample_unit_vector(sampler.get_2d());
 
         // Catch degenerate scatter direction
         if scatter_direction.near_zero() {
             scatter_direction = rec.normal;
         }
 
-        let scattered = Ray::new(rec.p, scatter_direction);
-        Some((self.albedo, scattered))
+        let scattered = Ray::with_time(rec.p, scatter_direction, r_in.time);
+        Some((self.albedo.value(rec.u, rec.v, &rec.p), scattered))
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = rec.normal.dot(&scattered.direction.unit_vector());
+        cosine.max(0.0) / PI
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let cosine = rec.normal.dot(&scattered.direction.unit_vector());
+        self.albedo.value(rec.u, rec.v, &rec.p) * (cosine.max(0.0) / PI)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
     }
 }
 
 pub struct Metal {
-    pub albedo: Color,
+    pub albedo: Arc<dyn Texture>,
     pub fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self { albedo, fuzz: if fuzz < 1.0 { fuzz } else { 1.0 } }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
         let reflected = Vec3::reflect(&r_in.direction.unit_vector(), &rec.normal);
-        let scattered = Ray::new(rec.p, reflected + Vec3::random_in_unit_sphere() * self.fuzz);
+        let fuzz = Vec3::sample_in_unit_sphere(sampler.get_2d(), sampler.get_1d()) * self.fuzz;
+        let scattered = Ray::with_time(rec.p, reflected + fuzz, r_in.time);
         if scattered.direction.dot(&rec.normal) > 0.0 {
-            Some((self.albedo, scattered))
+            Some((self.albedo.value(rec.u, rec.v, &rec.p), scattered))
         } else {
             None
         }
     }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
 }
 
 pub struct Dielectric {
@@ -72,7 +124,7 @@ fn schlick(cosine: f64, ref_idx: f64) -> f64 {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
         let attenuation = Color::new(1.0, 1.0, 1.0);
         let refraction_ratio = if rec.front_face { 1.0 / self.ir } else { self.ir };
 
@@ -81,14 +133,43 @@ impl Material for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let mut rng = rand::thread_rng();
-        let direction = if cannot_refract || schlick(cos_theta, refraction_ratio) > rng.r#gen::<f64>() {
+        let direction = if cannot_refract || schlick(cos_theta, refraction_ratio) > sampler.get_1d() {
             Vec3::reflect(&unit_direction, &rec.normal)
         } else {
             Vec3::refract(&unit_direction, &rec.normal, refraction_ratio)
         };
 
-        let scattered = Ray::new(rec.p, direction);
+        let scattered = Ray::with_time(rec.p, direction, r_in.time);
         Some((attenuation, scattered))
     }
+
+    /// Clear glass is conventionally white in albedo passes.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+/// Emits `emit` from the front face of whatever it is applied to and absorbs all incoming light.
+pub struct DiffuseLight {
+    pub emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn from_texture(emit: Arc<dyn Texture>) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        if rec.front_face { self.emit.value(rec.u, rec.v, &rec.p) } else { Color::zero() }
+    }
 }
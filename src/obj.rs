@@ -0,0 +1,484 @@
+//! Wavefront OBJ and MTL import.
+//!
+//! Supports `v`/`vt`/`vn`, faces with any number of vertices (fan-triangulated,
+//! negative indices allowed), `g`/`o` groups, `usemtl` and `mtllib`. Statements
+//! that do not affect geometry or shading (`s`, `l`, `p`, ...) are ignored.
+
+use crate::hittable::Hittable;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::triangle::TriangleMesh;
+use crate::vec3::{Color, Point3, Vec3};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct ObjError {
+    pub path: PathBuf,
+    /// 1-based line, or 0 if the file could not be read at all.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.path.display(), self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// A material parsed from an MTL file.
+#[derive(Clone, Debug)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub kd: Color,
+    pub ks: Color,
+    pub ns: f64,
+    pub ni: f64,
+    pub d: f64,
+    pub illum: u32,
+}
+
+impl MtlMaterial {
+    fn new(name: String) -> Self {
+        Self { name, kd: Color::new(0.8, 0.8, 0.8), ks: Color::zero(), ns: 0.0, ni: 1.5, d: 1.0, illum: 2 }
+    }
+
+    /// Maps the MTL parameters onto the closest built-in material:
+    /// transparent or refractive illumination models become `Dielectric`,
+    /// reflective ones (or a black diffuse with a specular colour) `Metal`,
+    /// with the Phong exponent `Ns` turned into fuzz, and anything else `Lambertian`.
+    pub fn to_material(&self) -> Arc<dyn Material + Send + Sync> {
+        let max = |c: Color| c.x.max(c.y).max(c.z);
+        if self.d < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            Arc::new(Dielectric::new(self.ni))
+        } else if matches!(self.illum, 3 | 5 | 8) || (max(self.kd) == 0.0 && max(self.ks) > 0.0) {
+            let fuzz = (2.0 / (self.ns.max(0.0) + 2.0)).sqrt();
+            Arc::new(Metal::new(self.ks, fuzz))
+        } else {
+            Arc::new(Lambertian::new(self.kd))
+        }
+    }
+}
+
+/// One group/material run of faces from an OBJ file.
+pub struct ObjMesh {
+    pub group: String,
+    pub material: Option<String>,
+    pub mesh: Arc<TriangleMesh>,
+}
+
+/// The triangles of one mesh and the name of the MTL material they use, if any.
+pub type MeshTriangles<'a> = (Option<&'a str>, Vec<Arc<dyn Hittable>>);
+
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: HashMap<String, MtlMaterial>,
+}
+
+impl ObjModel {
+    /// Triangles for every mesh, shaded with their MTL material. Faces without
+    /// one use `fallback`; `override_mat`, when given, replaces all materials.
+    ///
+    /// Returns one entry per mesh, paired with the name of the MTL material it
+    /// was shaded with (`None` for `fallback` or `override_mat`).
+    pub fn hittables(
+        &self,
+        fallback: Arc<dyn Material + Send + Sync>,
+        override_mat: Option<Arc<dyn Material + Send + Sync>>,
+    ) -> Vec<MeshTriangles<'_>> {
+        let converted: HashMap<&str, Arc<dyn Material + Send + Sync>> =
+            self.materials.iter().map(|(name, m)| (name.as_str(), m.to_material())).collect();
+
+        self.meshes
+            .iter()
+            .map(|m| {
+                let (name, mat) = match (&override_mat, &m.material) {
+                    (Some(mat), _) => (None, mat.clone()),
+                    (None, Some(name)) => (Some(name.as_str()), converted[name.as_str()].clone()),
+                    (None, None) => (None, fallback.clone()),
+                };
+                (name, TriangleMesh::triangles(&m.mesh, mat))
+            })
+            .collect()
+    }
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    let src = std::fs::read_to_string(path)
+        .map_err(|e| ObjError { path: path.to_path_buf(), line: 0, message: e.to_string() })?;
+    parse_obj(&src, path)
+}
+
+/// A face corner: indices into the file-wide position/uv/normal arrays.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Faces accumulated for the current group/material.
+struct Chunk {
+    group: String,
+    material: Option<String>,
+    faces: Vec<[Corner; 3]>,
+}
+
+fn parse_obj(src: &str, path: &Path) -> Result<ObjModel, ObjError> {
+    let err = |line: usize, message: String| ObjError { path: path.to_path_buf(), line, message };
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut materials: HashMap<String, MtlMaterial> = HashMap::new();
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut group = String::from("default");
+    let mut material: Option<String> = None;
+
+    for (idx, raw) in src.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = raw.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = floats::<3>(&args, 3).map_err(|m| err(line_no, m))?;
+                positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = floats::<3>(&args, 3).map_err(|m| err(line_no, m))?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v] = floats::<2>(&args, 1).map_err(|m| err(line_no, m))?;
+                uvs.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err(line_no, format!("face needs at least 3 vertices, found {}", args.len())));
+                }
+                let corners = args
+                    .iter()
+                    .map(|spec| parse_corner(spec, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<Corner>, String>>()
+                    .map_err(|m| err(line_no, m))?;
+
+                let starts_new_chunk = chunks.last().is_none_or(|c| c.group != group || c.material != material);
+                if starts_new_chunk {
+                    chunks.push(Chunk { group: group.clone(), material: material.clone(), faces: Vec::new() });
+                }
+                let chunk = chunks.last_mut().expect("chunk was just pushed");
+                for i in 1..corners.len() - 1 {
+                    chunk.faces.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                group = if args.is_empty() { String::from("default") } else { args.join(" ") };
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if !materials.contains_key(&name) {
+                    return Err(err(line_no, format!("unknown material '{}'", name)));
+                }
+                material = Some(name);
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(err(line_no, "mtllib needs a file name".into()));
+                }
+                for file in &args {
+                    let mtl_path = base_dir.join(file);
+                    let mtl_src = std::fs::read_to_string(&mtl_path)
+                        .map_err(|e| err(line_no, format!("cannot read material library {}: {}", mtl_path.display(), e)))?;
+                    for m in parse_mtl(&mtl_src, &mtl_path)? {
+                        materials.insert(m.name.clone(), m);
+                    }
+                }
+            }
+            _ => {} // smoothing groups, lines, points, free-form geometry...
+        }
+    }
+
+    let meshes = chunks
+        .into_iter()
+        .map(|chunk| ObjMesh {
+            mesh: Arc::new(build_mesh(&chunk.faces, &positions, &uvs, &normals)),
+            group: chunk.group,
+            material: chunk.material,
+        })
+        .collect();
+
+    Ok(ObjModel { meshes, materials })
+}
+
+/// De-indexes OBJ corners (which index each attribute separately) into a
+/// mesh with one shared index per unique position/uv/normal combination.
+/// Normals and uvs are only kept if every corner in the run has them.
+fn build_mesh(faces: &[[Corner; 3]], positions: &[Point3], uvs: &[(f64, f64)], normals: &[Vec3]) -> TriangleMesh {
+    let all_uvs = faces.iter().flatten().all(|c| c.1.is_some());
+    let all_normals = faces.iter().flatten().all(|c| c.2.is_some());
+
+    let mut remap: HashMap<Corner, usize> = HashMap::new();
+    let (mut mesh_positions, mut mesh_uvs, mut mesh_normals) = (Vec::new(), Vec::new(), Vec::new());
+    let mut indices = Vec::with_capacity(faces.len());
+
+    for face in faces {
+        let mut tri = [0usize; 3];
+        for (slot, &(p, t, n)) in tri.iter_mut().zip(face) {
+            let key = (p, t.filter(|_| all_uvs), n.filter(|_| all_normals));
+            *slot = *remap.entry(key).or_insert_with(|| {
+                mesh_positions.push(positions[p]);
+                if let Some(t) = key.1 {
+                    mesh_uvs.push(uvs[t]);
+                }
+                if let Some(n) = key.2 {
+                    mesh_normals.push(normals[n]);
+                }
+                mesh_positions.len() - 1
+            });
+        }
+        indices.push(tri);
+    }
+
+    TriangleMesh::new(mesh_positions, mesh_normals, mesh_uvs, indices).expect("OBJ indices were validated while parsing")
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving 1-based and negative indices.
+fn parse_corner(spec: &str, n_positions: usize, n_uvs: usize, n_normals: usize) -> Result<Corner, String> {
+    let mut parts = spec.split('/');
+    let resolve = |part: Option<&str>, count: usize, what: &str| -> Result<Option<usize>, String> {
+        let Some(text) = part.filter(|t| !t.is_empty()) else { return Ok(None) };
+        let index: i64 = text.parse().map_err(|_| format!("invalid {} index '{}' in face vertex '{}'", what, text, spec))?;
+        let resolved = if index > 0 { index - 1 } else { count as i64 + index };
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("{} index {} out of range (have {})", what, index, count));
+        }
+        Ok(Some(resolved as usize))
+    };
+
+    let p = resolve(parts.next(), n_positions, "position")?.ok_or_else(|| format!("face vertex '{}' has no position", spec))?;
+    let t = resolve(parts.next(), n_uvs, "texture coordinate")?;
+    let n = resolve(parts.next(), n_normals, "normal")?;
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex '{}'", spec));
+    }
+    Ok((p, t, n))
+}
+
+/// Parses at least `required` and at most `N` leading numbers; missing ones are 0.
+/// Extra trailing values (such as a `w` coordinate) are ignored.
+fn floats<const N: usize>(args: &[&str], required: usize) -> Result<[f64; N], String> {
+    if args.len() < required {
+        return Err(format!("expected {} numbers, found {}", required, args.len()));
+    }
+    let mut out = [0.0; N];
+    for (slot, arg) in out.iter_mut().zip(args) {
+        *slot = arg.parse().map_err(|_| format!("invalid number '{}'", arg))?;
+    }
+    Ok(out)
+}
+
+fn parse_mtl(src: &str, path: &Path) -> Result<Vec<MtlMaterial>, ObjError> {
+    let err = |line: usize, message: String| ObjError { path: path.to_path_buf(), line, message };
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (idx, raw) in src.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = raw.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(err(line_no, "newmtl needs a material name".into()));
+            }
+            materials.push(MtlMaterial::new(args.join(" ")));
+            continue;
+        }
+
+        let Some(current) = materials.last_mut() else {
+            if matches!(keyword, "Kd" | "Ks" | "Ns" | "Ni" | "d" | "Tr" | "illum") {
+                return Err(err(line_no, format!("'{}' before any newmtl", keyword)));
+            }
+            continue;
+        };
+        let color = |args: &[&str]| floats::<3>(args, 1).map(|[r, g, b]| if args.len() == 1 { Color::new(r, r, r) } else { Color::new(r, g, b) });
+        let scalar = |args: &[&str]| floats::<1>(args, 1).map(|[x]| x);
+
+        match keyword {
+            "Kd" => current.kd = color(&args).map_err(|m| err(line_no, m))?,
+            "Ks" => current.ks = color(&args).map_err(|m| err(line_no, m))?,
+            "Ns" => current.ns = scalar(&args).map_err(|m| err(line_no, m))?,
+            "Ni" => current.ni = scalar(&args).map_err(|m| err(line_no, m))?,
+            "d" => current.d = scalar(&args).map_err(|m| err(line_no, m))?,
+            "Tr" => current.d = 1.0 - scalar(&args).map_err(|m| err(line_no, m))?,
+            "illum" => {
+                current.illum = args
+                    .first()
+                    .and_then(|a| a.parse().ok())
+                    .ok_or_else(|| err(line_no, format!("invalid illumination model '{}'", args.join(" "))))?;
+            }
+            _ => {} // Ka, Ke, texture maps...
+        }
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::HitRecord;
+    use crate::ray::Ray;
+
+    fn obj(src: &str) -> ObjModel {
+        parse_obj(src, Path::new("test.obj")).unwrap()
+    }
+
+    fn obj_error(src: &str) -> ObjError {
+        parse_obj(src, Path::new("test.obj")).err().expect("parse should fail")
+    }
+
+    fn mtl(src: &str) -> Vec<MtlMaterial> {
+        parse_mtl(src, Path::new("test.mtl")).unwrap()
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn corners_resolve_absolute_and_relative_indices() {
+        assert_eq!(parse_corner("1", 3, 0, 0), Ok((0, None, None)));
+        assert_eq!(parse_corner("-1", 3, 0, 0), Ok((2, None, None)));
+        assert_eq!(parse_corner("2/1", 3, 2, 0), Ok((1, Some(0), None)));
+        assert_eq!(parse_corner("3//-2", 3, 0, 2), Ok((2, None, Some(0))));
+        assert_eq!(parse_corner("-3/-1/1", 3, 2, 1), Ok((0, Some(1), Some(0))));
+
+        assert!(parse_corner("0", 3, 0, 0).is_err());
+        assert!(parse_corner("4", 3, 0, 0).is_err());
+        assert!(parse_corner("-4", 3, 0, 0).is_err());
+        assert!(parse_corner("1/2", 3, 1, 0).is_err());
+        assert!(parse_corner("/1", 3, 1, 0).is_err());
+        assert!(parse_corner("1/1/1/1", 3, 1, 1).is_err());
+        assert!(parse_corner("x", 3, 0, 0).is_err());
+    }
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let model = obj(&format!("{}f 1 2 3 4\n", SQUARE));
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0].mesh;
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.positions.len(), 4);
+        assert!(mesh.uvs.is_empty() && mesh.normals.is_empty());
+    }
+
+    #[test]
+    fn corners_are_deindexed_per_attribute_combination() {
+        // The shared edge reuses its vertices; position 1 with two different
+        // normals becomes two vertices.
+        let src = format!("{}vt 0 0\nvt 1 1\nvn 0 0 1\nvn 0 0 -1\nf 1/1/1 2/2/1 3/1/1\nf 1/1/2 3/1/1 4/2/1\n", SQUARE);
+        let mesh = &obj(&src).meshes[0].mesh;
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [3, 2, 4]]);
+        assert_eq!(mesh.positions.len(), 5);
+        assert_eq!(mesh.positions[3], mesh.positions[0]);
+        assert_eq!(mesh.normals[3], Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(mesh.uvs, vec![(0.0, 0.0), (1.0, 1.0), (0.0, 0.0), (0.0, 0.0), (1.0, 1.0)]);
+    }
+
+    #[test]
+    fn attributes_missing_on_some_corners_are_dropped() {
+        let src = format!("{}vt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3//1\nf 1/1 3/1 4/1\n", SQUARE);
+        let mesh = &obj(&src).meshes[0].mesh;
+        assert!(mesh.uvs.is_empty());
+        assert!(mesh.normals.is_empty());
+        // Without the attributes the corners collapse onto the positions.
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+
+        let src = format!("{}vn 0 0 1\nf 1//1 2//1 3//1\n", SQUARE);
+        let mesh = &obj(&src).meshes[0].mesh;
+        assert_eq!(mesh.normals.len(), 3);
+        assert!(mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn groups_split_meshes() {
+        let model = obj(&format!("{}g left\nf 1 2 3\no right side\nf 1 3 4\nf -4 -2 -1\n", SQUARE));
+        let groups: Vec<(&str, usize)> = model.meshes.iter().map(|m| (m.group.as_str(), m.mesh.indices.len())).collect();
+        assert_eq!(groups, vec![("left", 1), ("right side", 2)]);
+    }
+
+    #[test]
+    fn obj_errors_carry_line_numbers() {
+        let e = obj_error("v 0 0 0\nv 1 0 0\nv 1 x 0\n");
+        assert_eq!((e.line, e.message.as_str()), (3, "invalid number 'x'"));
+        let e = obj_error(&format!("{}f 1 2\n", SQUARE));
+        assert_eq!(e.line, 5);
+        let e = obj_error(&format!("{}\nf 1 2 5\n", SQUARE));
+        assert_eq!(e.line, 6);
+        assert!(e.message.contains("out of range"), "{}", e.message);
+        let e = obj_error("usemtl missing\n");
+        assert_eq!((e.line, e.to_string().as_str()), (1, "test.obj:1: unknown material 'missing'"));
+    }
+
+    #[test]
+    fn mtl_parameters_are_read() {
+        let materials = mtl("# comment\nnewmtl red paint\nKd 0.8 0.1 0.1\nKs 0.5\nNs 10\nillum 2\n\nnewmtl glass\nNi 1.33\nTr 0.25\nnewmtl fog\nd 0.5\n");
+        assert_eq!(materials.len(), 3);
+        assert_eq!(materials[0].name, "red paint");
+        assert_eq!(materials[0].kd, Color::new(0.8, 0.1, 0.1));
+        assert_eq!(materials[0].ks, Color::new(0.5, 0.5, 0.5));
+        assert_eq!(materials[0].ns, 10.0);
+        assert_eq!((materials[1].ni, materials[1].d), (1.33, 0.75));
+        assert_eq!(materials[2].d, 0.5);
+
+        let e = parse_mtl("Kd 1 1 1\n", Path::new("test.mtl")).err().unwrap();
+        assert_eq!((e.line, e.message.as_str()), (1, "'Kd' before any newmtl"));
+        let e = parse_mtl("newmtl a\nillum x\n", Path::new("test.mtl")).err().unwrap();
+        assert_eq!(e.line, 2);
+    }
+
+    /// Albedo and BSDF density of `material` for a ray hitting the z = 0
+    /// plane head-on, which tell the built-in materials apart.
+    fn probe(material: &MtlMaterial) -> (Color, f64) {
+        let mat = material.to_material();
+        let r = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = HitRecord::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0, 0.0, 0.0, &r, mat.clone());
+        let out = Ray::new(rec.p, Vec3::new(0.0, 0.0, 1.0));
+        (mat.albedo(&rec), mat.scattering_pdf(&r, &rec, &out))
+    }
+
+    #[test]
+    fn illumination_models_map_to_materials() {
+        let materials = mtl("newmtl m\nKd 0.2 0.4 0.6\nKs 0.9 0.8 0.7\nNs 100\n");
+        let mut m = materials[0].clone();
+        let white = Color::new(1.0, 1.0, 1.0);
+        let (kd, ks) = (m.kd, m.ks);
+
+        let (albedo, pdf) = probe(&m);
+        assert_eq!(albedo, kd, "illum 2 is diffuse");
+        assert!(pdf > 0.0);
+        for illum in [3, 5, 8] {
+            m.illum = illum;
+            assert_eq!(probe(&m), (ks, 0.0), "illum {} is metal", illum);
+        }
+        for illum in [4, 6, 7, 9] {
+            m.illum = illum;
+            assert_eq!(probe(&m), (white, 0.0), "illum {} is glass", illum);
+        }
+
+        // Any transparency makes glass; a black diffuse with a specular colour is metal.
+        m.illum = 2;
+        m.d = 0.9;
+        assert_eq!(probe(&m).0, white);
+        m.d = 1.0;
+        m.kd = Color::zero();
+        assert_eq!(probe(&m), (ks, 0.0));
+    }
+}
@@ -0,0 +1,240 @@
+//! Flat primitives: infinite planes, parallelograms, disks and boxes.
+//!
+//! [`Quad`] and [`Disk`] can be sampled as area lights; [`Plane`] is
+//! unbounded, so it emits when hit but cannot be sampled. A [`Cuboid`] is six
+//! quads and is named so as not to shadow `std`'s `Box`.
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Boxes of flat shapes are padded by this much so they are never zero-thickness.
+const PAD: f64 = 1e-6;
+
+/// Where `r` meets the plane through `point` with unit normal `normal`, if
+/// the ray isn't parallel to it and the hit lies in `[t_min, t_max]`.
+fn hit_plane(point: Point3, normal: Vec3, r: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+    let denom = normal.dot(&r.direction);
+    if denom.abs() < 1e-12 {
+        return None;
+    }
+    let t = (point - r.origin).dot(&normal) / denom;
+    (t_min..=t_max).contains(&t).then_some(t)
+}
+
+/// Area sampling converted to solid angle: `distance² / (cos θ · area)`.
+fn area_pdf(t: f64, direction: &Vec3, normal: &Vec3, area: f64) -> f64 {
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = (direction.dot(normal) / direction.length()).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+/// An infinite plane. Textures repeat once per unit along two axes in the plane.
+pub struct Plane {
+    pub point: Point3,
+    basis: Onb,
+    pub mat: Arc<dyn Material + Send + Sync>,
+}
+
+impl Plane {
+    /// The plane through `point` whose front faces `normal`.
+    pub fn new(point: Point3, normal: Vec3, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self { point, basis: Onb::new(normal), mat }
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.basis.w
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_plane(self.point, self.basis.w, r, t_min, t_max)?;
+        let p = r.at(t);
+        let offset = p - self.point;
+        let (u, v) = (offset.dot(&self.basis.u).rem_euclid(1.0), offset.dot(&self.basis.v).rem_euclid(1.0));
+        Some(HitRecord::new(p, self.basis.w, t, u, v, r, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// The parallelogram with corners `corner`, `corner + u`, `corner + v` and
+/// `corner + u + v`. Its front faces `u × v`; `(u, v)` texture coordinates
+/// run from 0 to 1 along the edges.
+pub struct Quad {
+    pub corner: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub mat: Arc<dyn Material + Send + Sync>,
+    normal: Vec3,
+    /// `n / |n|²` for `n = u × v`, projecting hit points onto the edges.
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(corner: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        let n = u.cross(&v);
+        Self { corner, u, v, mat, normal: n.unit_vector(), w: n / n.length_squared(), area: n.length() }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_plane(self.corner, self.normal, r, t_min, t_max)?;
+        let p = r.at(t);
+        let planar = p - self.corner;
+        let alpha = self.w.dot(&planar.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some(HitRecord::new(p, self.normal, t, alpha, beta, r, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let corners = [self.corner + self.u, self.corner + self.v, self.corner + self.u + self.v];
+        let bbox = corners.iter().fold(Aabb::new(self.corner, self.corner), |acc, c| acc.surrounding(&Aabb::new(*c, *c)));
+        let pad = Vec3::new(PAD, PAD, PAD);
+        Some(Aabb::new(bbox.min - pad, bbox.max + pad))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        match self.hit(&Ray::new(*origin, *direction), 0.001, f64::INFINITY) {
+            Some(rec) => area_pdf(rec.t, direction, &self.normal, self.area),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let [r1, r2] = sampler.get_2d();
+        self.corner + self.u * r1 + self.v * r2 - *origin
+    }
+}
+
+/// A flat disk. Its front faces `normal`; texture coordinates map the
+/// square around it to `[0, 1]²`, like a label.
+pub struct Disk {
+    pub center: Point3,
+    pub radius: f64,
+    basis: Onb,
+    pub mat: Arc<dyn Material + Send + Sync>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self { center, radius, basis: Onb::new(normal), mat }
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.basis.w
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_plane(self.center, self.basis.w, r, t_min, t_max)?;
+        let p = r.at(t);
+        let offset = p - self.center;
+        if offset.length_squared() > self.radius * self.radius {
+            return None;
+        }
+        let u = 0.5 + offset.dot(&self.basis.u) / (2.0 * self.radius);
+        let v = 0.5 + offset.dot(&self.basis.v) / (2.0 * self.radius);
+        Some(HitRecord::new(p, self.basis.w, t, u, v, r, self.mat.clone()))
+    }
+
+    /// Along each axis the rim reaches `radius · √(1 − n²)` from the center.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let n = self.basis.w;
+        let reach = |c: f64| self.radius * (1.0 - c * c).max(0.0).sqrt() + PAD;
+        let extent = Vec3::new(reach(n.x), reach(n.y), reach(n.z));
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        match self.hit(&Ray::new(*origin, *direction), 0.001, f64::INFINITY) {
+            Some(rec) => area_pdf(rec.t, direction, &self.basis.w, PI * self.radius * self.radius),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let d = Vec3::sample_unit_disk(sampler.get_2d()) * self.radius;
+        self.center + self.basis.u * d.x + self.basis.v * d.y - *origin
+    }
+}
+
+/// A box made of six outward-facing [`Quad`]s.
+pub struct Cuboid {
+    pub sides: [Quad; 6],
+    bbox: Aabb,
+}
+
+impl Cuboid {
+    /// The axis-aligned box between two opposite corners given in any order.
+    pub fn new(a: Point3, b: Point3, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        let bbox = Aabb::new(a, b);
+        let size = bbox.max - bbox.min;
+        Self::from_edges(bbox.min, Vec3::new(size.x, 0.0, 0.0), Vec3::new(0.0, size.y, 0.0), Vec3::new(0.0, 0.0, size.z), mat)
+    }
+
+    /// The box spanned by the edges `x`, `y` and `z` from `corner`, which
+    /// needn't be axis-aligned; `x`, `y`, `z` should form a right-handed set.
+    pub fn from_edges(corner: Point3, x: Vec3, y: Vec3, z: Vec3, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        let far = corner + x + y + z;
+        let side = |c: Point3, u: Vec3, v: Vec3| Quad::new(c, u, v, mat.clone());
+        let sides = [
+            side(corner, z, y),
+            side(corner, x, z),
+            side(corner, y, x),
+            side(far, -y, -z),
+            side(far, -z, -x),
+            side(far, -x, -y),
+        ];
+        let bbox = sides.iter().filter_map(|s| s.bounding_box()).reduce(|a, b| a.surrounding(&b)).expect("a box has sides");
+        Self { sides, bbox }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+        let mut closest = None;
+        for side in &self.sides {
+            if let Some(rec) = side.hit(r, t_min, t_max) {
+                t_max = rec.t;
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+
+    /// Sides are picked uniformly, so the density is the mean of theirs.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.sides.iter().map(|s| s.pdf_value(origin, direction)).sum::<f64>() / 6.0
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let index = ((sampler.get_1d() * 6.0) as usize).min(5);
+        self.sides[index].random(origin, sampler)
+    }
+}
@@ -0,0 +1,522 @@
+//! Analytic curved primitives: cylinders, cones, tori and paraboloids.
+//!
+//! Each shape stands upright along +y from its `base` (tori lie flat around
+//! their `center`); use a [`crate::transform::Transformed`] to tilt one.
+//! Texture `u` runs once around the axis and `v` up it, so textures wrap
+//! around the shape like a label. None of them can be sampled as lights.
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Coefficients this close to zero are treated as zero by the root solvers.
+const EPSILON: f64 = 1e-9;
+
+/// Angle of `(x, z)` around the y axis as a texture coordinate in [0, 1].
+fn around_y(x: f64, z: f64) -> f64 {
+    (z.atan2(x) + PI) / (2.0 * PI)
+}
+
+/// Roots of `a t² + 2 half_b t + c`, ascending. Computed so that neither
+/// root cancels: as `a` vanishes one root goes to infinity and the other
+/// to the root of the linear equation left, as for rays parallel to a cone.
+fn quadratic_roots(a: f64, half_b: f64, c: f64) -> Option<[f64; 2]> {
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -(half_b + discriminant.sqrt().copysign(half_b));
+    if q == 0.0 {
+        return (a != 0.0).then_some([0.0, 0.0]);
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some([t0.min(t1), t0.max(t1)])
+}
+
+/// A possible hit: `(t, point in the shape's frame, outward normal, u, v)`.
+type Candidate = (f64, Point3, Vec3, f64, f64);
+
+/// Where `r` crosses the plane `y = height` inside the circle of `radius`
+/// around the axis, facing `normal_y` (±1).
+fn cap(r: &Ray, height: f64, radius: f64, normal_y: f64, t_min: f64, t_max: f64) -> Option<Candidate> {
+    if r.direction.y.abs() < EPSILON {
+        return None;
+    }
+    let t = (height - r.origin.y) / r.direction.y;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    let p = r.at(t);
+    if p.x * p.x + p.z * p.z > radius * radius {
+        return None;
+    }
+    let (u, v) = (0.5 + p.x / (2.0 * radius), 0.5 + p.z / (2.0 * radius));
+    Some((t, p, Vec3::new(0.0, normal_y, 0.0), u, v))
+}
+
+fn nearest(candidates: impl IntoIterator<Item = Option<Candidate>>) -> Option<Candidate> {
+    candidates.into_iter().flatten().min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+/// Builds the hit record for a candidate found with `local`, the ray moved so
+/// the shape's base is at the origin.
+fn record(candidate: Option<Candidate>, base: Point3, r: &Ray, mat: &Arc<dyn Material + Send + Sync>) -> Option<HitRecord> {
+    let (t, p, normal, u, v) = candidate?;
+    Some(HitRecord::new(p + base, normal, t, u, v, r, mat.clone()))
+}
+
+/// A cylinder of `radius` from `base` up `height` along +y, closed by two
+/// disks when `capped`.
+pub struct Cylinder {
+    pub base: Point3,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub mat: Arc<dyn Material + Send + Sync>,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3, radius: f64, height: f64, capped: bool, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self { base, radius, height, capped, mat }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = Ray::with_time(r.origin - self.base, r.direction, r.time);
+        let (o, d) = (local.origin, local.direction);
+        let side = quadratic_roots(d.x * d.x + d.z * d.z, o.x * d.x + o.z * d.z, o.x * o.x + o.z * o.z - self.radius * self.radius)
+            .into_iter()
+            .flatten()
+            .filter(|t| (t_min..=t_max).contains(t))
+            .map(|t| (t, local.at(t)))
+            .find(|(_, p)| (0.0..=self.height).contains(&p.y))
+            .map(|(t, p)| (t, p, Vec3::new(p.x, 0.0, p.z) / self.radius, around_y(p.x, p.z), p.y / self.height));
+        let (bottom, top) = if self.capped {
+            (cap(&local, 0.0, self.radius, -1.0, t_min, t_max), cap(&local, self.height, self.radius, 1.0, t_min, t_max))
+        } else {
+            (None, None)
+        };
+        record(nearest([side, bottom, top]), self.base, r, &self.mat)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, 0.0, self.radius);
+        Some(Aabb::new(self.base - extent, self.base + extent + Vec3::new(0.0, self.height, 0.0)))
+    }
+}
+
+/// A cone with a base of `radius` at `base`, narrowing to its apex `height`
+/// above it, closed at the base when `capped`.
+pub struct Cone {
+    pub base: Point3,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub mat: Arc<dyn Material + Send + Sync>,
+}
+
+impl Cone {
+    pub fn new(base: Point3, radius: f64, height: f64, capped: bool, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self { base, radius, height, capped, mat }
+    }
+}
+
+impl Hittable for Cone {
+    /// The side lies on `x² + z² = k² (height − y)²` with `k = radius / height`.
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = Ray::with_time(r.origin - self.base, r.direction, r.time);
+        let (o, d) = (local.origin, local.direction);
+        let k2 = (self.radius / self.height).powi(2);
+        let h = self.height - o.y;
+        let side = quadratic_roots(
+            d.x * d.x + d.z * d.z - k2 * d.y * d.y,
+            o.x * d.x + o.z * d.z + k2 * h * d.y,
+            o.x * o.x + o.z * o.z - k2 * h * h,
+        )
+        .into_iter()
+        .flatten()
+        .filter(|t| (t_min..=t_max).contains(t))
+        .map(|t| (t, local.at(t)))
+        .find(|(_, p)| (0.0..=self.height).contains(&p.y))
+        .map(|(t, p)| {
+            let normal = Vec3::new(p.x, k2 * (self.height - p.y), p.z);
+            // The apex has no normal of its own; point it along the axis.
+            let normal = if normal.near_zero() { Vec3::new(0.0, 1.0, 0.0) } else { normal.unit_vector() };
+            (t, p, normal, around_y(p.x, p.z), p.y / self.height)
+        });
+        let bottom = if self.capped { cap(&local, 0.0, self.radius, -1.0, t_min, t_max) } else { None };
+        record(nearest([side, bottom]), self.base, r, &self.mat)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, 0.0, self.radius);
+        Some(Aabb::new(self.base - extent, self.base + extent + Vec3::new(0.0, self.height, 0.0)))
+    }
+}
+
+/// A paraboloid bowl `y = height · (x² + z²) / radius²` with its vertex at
+/// `base`, open at the rim `height` above it unless `capped`.
+pub struct Paraboloid {
+    pub base: Point3,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub mat: Arc<dyn Material + Send + Sync>,
+}
+
+impl Paraboloid {
+    pub fn new(base: Point3, radius: f64, height: f64, capped: bool, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self { base, radius, height, capped, mat }
+    }
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = Ray::with_time(r.origin - self.base, r.direction, r.time);
+        let (o, d) = (local.origin, local.direction);
+        let a = self.height / (self.radius * self.radius);
+        let side = quadratic_roots(a * (d.x * d.x + d.z * d.z), a * (o.x * d.x + o.z * d.z) - 0.5 * d.y, a * (o.x * o.x + o.z * o.z) - o.y)
+            .into_iter()
+            .flatten()
+            .filter(|t| (t_min..=t_max).contains(t))
+            .map(|t| (t, local.at(t)))
+            .find(|(_, p)| p.y <= self.height)
+            .map(|(t, p)| {
+                // The gradient of a (x² + z²) − y, pointing away from the inside of the bowl.
+                let normal = Vec3::new(2.0 * a * p.x, -1.0, 2.0 * a * p.z).unit_vector();
+                (t, p, normal, around_y(p.x, p.z), p.y / self.height)
+            });
+        let top = if self.capped { cap(&local, self.height, self.radius, 1.0, t_min, t_max) } else { None };
+        record(nearest([side, top]), self.base, r, &self.mat)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, 0.0, self.radius);
+        Some(Aabb::new(self.base - extent, self.base + extent + Vec3::new(0.0, self.height, 0.0)))
+    }
+}
+
+/// A ring torus around the y axis through `center`: a tube of
+/// `minor_radius` swept around a circle of `major_radius` in the xz plane.
+pub struct Torus {
+    pub center: Point3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub mat: Arc<dyn Material + Send + Sync>,
+}
+
+impl Torus {
+    pub fn new(center: Point3, major_radius: f64, minor_radius: f64, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self { center, major_radius, minor_radius, mat }
+    }
+}
+
+impl Hittable for Torus {
+    /// Solves the quartic `(|p|² + R² − r²)² = 4R² (x² + z²)` along the ray.
+    /// The ray is first normalized and started near the torus, keeping the
+    /// coefficients well scaled however far away the origin is.
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (big, small) = (self.major_radius, self.minor_radius);
+        let length = r.direction.length();
+        let d = r.direction / length;
+        let to_center = self.center - r.origin;
+        let start = (to_center.dot(&d) - (big + small)).max(0.0);
+        let o = r.origin + d * start - self.center;
+
+        let n = o.dot(&d);
+        let k = o.length_squared() + big * big - small * small;
+        let ring = 4.0 * big * big;
+        let coefficients = [
+            k * k - ring * (o.x * o.x + o.z * o.z),
+            4.0 * n * k - 2.0 * ring * (o.x * d.x + o.z * d.z),
+            4.0 * n * n + 2.0 * k - ring * (d.x * d.x + d.z * d.z),
+            4.0 * n,
+            1.0,
+        ];
+        // Convert back to the caller's parametrization before range checks.
+        let t = solve_quartic(coefficients)
+            .into_iter()
+            .map(|s| (s + start) / length)
+            .filter(|t| (t_min..=t_max).contains(t))
+            .min_by(f64::total_cmp)?;
+
+        let p = r.at(t);
+        let local = p - self.center;
+        let ring_point = Vec3::new(local.x, 0.0, local.z).unit_vector() * big;
+        let outward_normal = (local - ring_point) / small;
+        let u = around_y(local.x, local.z);
+        let v = (local.y.atan2((local.x * local.x + local.z * local.z).sqrt() - big) + PI) / (2.0 * PI);
+        Some(HitRecord::new(p, outward_normal, t, u, v, r, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let reach = self.major_radius + self.minor_radius;
+        let extent = Vec3::new(reach, self.minor_radius, reach);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+/// Real roots of `c[2] x² + c[1] x + c[0]`.
+fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let discriminant = p * p - q;
+    if discriminant.abs() < EPSILON {
+        vec![-p]
+    } else if discriminant < 0.0 {
+        Vec::new()
+    } else {
+        let s = discriminant.sqrt();
+        vec![s - p, -s - p]
+    }
+}
+
+/// Real roots of `c[3] x³ + … + c[0]` by Cardano's formula.
+fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    let (a, b, c0) = (c[2] / c[3], c[1] / c[3], c[0] / c[3]);
+    // Substituting x = y − a/3 leaves y³ + 3p y + 2q = 0.
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c0) / 2.0;
+    let cb_p = p * p * p;
+    let discriminant = q * q + cb_p;
+
+    let roots = if discriminant.abs() < EPSILON {
+        if q.abs() < EPSILON {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if discriminant < 0.0 {
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![t * phi.cos(), -t * (phi + PI / 3.0).cos(), -t * (phi - PI / 3.0).cos()]
+    } else {
+        let s = discriminant.sqrt();
+        vec![(s - q).cbrt() - (s + q).cbrt()]
+    };
+    roots.into_iter().map(|y| y - a / 3.0).collect()
+}
+
+/// Real roots of `c[4] x⁴ + … + c[0]` by Ferrari's method, each refined with
+/// a few Newton steps since the closed form loses precision.
+fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    let (a, b, c1, d) = (c[3] / c[4], c[2] / c[4], c[1] / c[4], c[0] / c[4]);
+    // Substituting x = y − a/4 leaves y⁴ + p y² + q y + r = 0.
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c1;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c1 / 4.0 + d;
+
+    let mut roots = if r.abs() < EPSILON {
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        // One root of the resolvent cubic splits the quartic into two quadratics.
+        let z = solve_cubic([r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let sqrt_or_zero = |x: f64| if x.abs() < EPSILON { Some(0.0) } else if x > 0.0 { Some(x.sqrt()) } else { None };
+        match (sqrt_or_zero(u), sqrt_or_zero(v)) {
+            (Some(u), Some(v)) => {
+                let v = if q < 0.0 { -v } else { v };
+                let mut roots = solve_quadratic([z - u, v, 1.0]);
+                roots.extend(solve_quadratic([z + u, -v, 1.0]));
+                roots
+            }
+            _ => Vec::new(),
+        }
+    };
+
+    let f = |x: f64| (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+    let df = |x: f64| ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+    for root in &mut roots {
+        *root -= a / 4.0;
+        for _ in 0..3 {
+            let slope = df(*root);
+            if slope.abs() > EPSILON {
+                *root -= f(*root) / slope;
+            }
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::vec3::Color;
+
+    fn mat() -> Arc<dyn Material + Send + Sync> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn ray(origin: [f64; 3], direction: [f64; 3]) -> Ray {
+        Ray::new(Point3::new(origin[0], origin[1], origin[2]), Vec3::new(direction[0], direction[1], direction[2]))
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "expected {}, got {}", expected, actual);
+    }
+
+    fn assert_vec_close(actual: Vec3, expected: [f64; 3]) {
+        for axis in 0..3 {
+            assert_close(actual[axis], expected[axis]);
+        }
+    }
+
+    #[test]
+    fn quartic_solver_finds_all_real_roots() {
+        // (x − 1)(x − 2)(x − 3)(x − 4)
+        let mut roots = solve_quartic([24.0, -50.0, 35.0, -10.0, 1.0]);
+        roots.sort_by(f64::total_cmp);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.into_iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert_close(root, expected);
+        }
+        // x⁴ + 1 has no real roots.
+        assert!(solve_quartic([1.0, 0.0, 0.0, 0.0, 1.0]).is_empty());
+    }
+
+    #[test]
+    fn cylinder_side_and_caps() {
+        let cylinder = Cylinder::new(Point3::new(0.0, 0.0, 0.0), 1.0, 2.0, true, mat());
+        let rec = cylinder.hit(&ray([-5.0, 1.0, 0.0], [1.0, 0.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 4.0);
+        assert_vec_close(rec.normal, [-1.0, 0.0, 0.0]);
+        assert!(rec.front_face);
+        assert_close(rec.v, 0.5);
+
+        let rec = cylinder.hit(&ray([0.2, 5.0, 0.0], [0.0, -1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 3.0);
+        assert_vec_close(rec.normal, [0.0, 1.0, 0.0]);
+
+        // Above the top and past the side.
+        assert!(cylinder.hit(&ray([-5.0, 2.5, 0.0], [1.0, 0.0, 0.0]), 0.001, f64::INFINITY).is_none());
+        assert!(cylinder.hit(&ray([-5.0, 1.0, 1.5], [1.0, 0.0, 0.0]), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn open_cylinder_is_hit_from_inside() {
+        let cylinder = Cylinder::new(Point3::new(0.0, 0.0, 0.0), 1.0, 2.0, false, mat());
+        assert!(cylinder.hit(&ray([0.0, 5.0, 0.0], [0.0, -1.0, 0.0]), 0.001, f64::INFINITY).is_none());
+        let rec = cylinder.hit(&ray([0.0, 1.0, 0.0], [1.0, 0.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 1.0);
+        assert!(!rec.front_face);
+        assert_vec_close(rec.normal, [-1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn cone_side_apex_and_cap() {
+        let cone = Cone::new(Point3::new(0.0, 1.0, 0.0), 1.0, 2.0, true, mat());
+        // Halfway up, the cone's radius is 0.5.
+        let rec = cone.hit(&ray([-5.0, 2.0, 0.0], [1.0, 0.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 4.5);
+        let expected = Vec3::new(-2.0, 1.0, 0.0).unit_vector();
+        assert_vec_close(rec.normal, [expected.x, expected.y, expected.z]);
+        assert!(rec.front_face);
+
+        let rec = cone.hit(&ray([0.0, 5.0, 0.0], [0.0, -1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 2.0);
+
+        let rec = cone.hit(&ray([0.5, -3.0, 0.0], [0.0, 1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 4.0);
+        assert_vec_close(rec.normal, [0.0, -1.0, 0.0]);
+
+        let open = Cone::new(Point3::new(0.0, 1.0, 0.0), 1.0, 2.0, false, mat());
+        let rec = open.hit(&ray([0.5, -3.0, 0.0], [0.0, 1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 5.0);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn ray_parallel_to_the_cone_side() {
+        // Travels along the generator line at 45° and meets the cone only at the base rim.
+        let cone = Cone::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1.0, false, mat());
+        let rec = cone.hit(&ray([-2.0, 1.0, 0.0], [1.0, -1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 1.0);
+        assert_vec_close(rec.p, [-1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn torus_outer_and_inner_walls() {
+        let torus = Torus::new(Point3::new(0.0, 0.0, 0.0), 2.0, 0.5, mat());
+        let rec = torus.hit(&ray([-10.0, 0.0, 0.0], [1.0, 0.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 7.5);
+        assert_vec_close(rec.normal, [-1.0, 0.0, 0.0]);
+        assert!(rec.front_face);
+
+        // From the hole the first surface is the inner wall of the tube.
+        let rec = torus.hit(&ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 1.5);
+        assert_vec_close(rec.normal, [-1.0, 0.0, 0.0]);
+        assert!(rec.front_face);
+
+        // Straight down through the hole, and down onto the top of the tube.
+        assert!(torus.hit(&ray([0.0, 5.0, 0.0], [0.0, -1.0, 0.0]), 0.001, f64::INFINITY).is_none());
+        let rec = torus.hit(&ray([0.0, 5.0, 2.0], [0.0, -1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 4.5);
+        assert_vec_close(rec.normal, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn torus_with_unnormalized_direction_and_distant_origin() {
+        let torus = Torus::new(Point3::new(1.0, 2.0, 3.0), 2.0, 0.5, mat());
+        let rec = torus.hit(&ray([-999.0, 2.0, 3.0], [4.0, 0.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 997.5 / 4.0);
+        assert_vec_close(rec.p, [-1.5, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn paraboloid_vertex_wall_and_cap() {
+        let bowl = Paraboloid::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1.0, false, mat());
+        let rec = bowl.hit(&ray([0.0, -2.0, 0.0], [0.0, 1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 2.0);
+        assert_vec_close(rec.normal, [0.0, -1.0, 0.0]);
+        assert!(rec.front_face);
+
+        // From above the open rim, the ray hits the inside of the bowl.
+        let rec = bowl.hit(&ray([0.5, 3.0, 0.0], [0.0, -1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 2.75);
+        assert!(!rec.front_face);
+
+        let capped = Paraboloid::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1.0, true, mat());
+        let rec = capped.hit(&ray([0.5, 3.0, 0.0], [0.0, -1.0, 0.0]), 0.001, f64::INFINITY).unwrap();
+        assert_close(rec.t, 2.0);
+        assert!(rec.front_face);
+
+        // Beside the rim.
+        assert!(bowl.hit(&ray([-5.0, 1.5, 0.0], [1.0, 0.0, 0.0]), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn hits_lie_inside_the_bounding_boxes() {
+        let shapes: Vec<Box<dyn Hittable>> = vec![
+            Box::new(Cylinder::new(Point3::new(1.0, -1.0, 0.5), 0.7, 1.3, true, mat())),
+            Box::new(Cone::new(Point3::new(-1.0, 0.0, 0.0), 0.8, 1.5, true, mat())),
+            Box::new(Torus::new(Point3::new(0.0, 0.5, -1.0), 1.0, 0.3, mat())),
+            Box::new(Paraboloid::new(Point3::new(0.0, -0.5, 0.0), 1.0, 2.0, true, mat())),
+        ];
+        for shape in &shapes {
+            let bbox = shape.bounding_box().unwrap();
+            for i in 0..200 {
+                let angle = i as f64 * 0.1;
+                let r = ray([6.0 * angle.cos(), (i % 7) as f64 - 3.0, 6.0 * angle.sin()], [-angle.cos(), 0.2 - (i % 5) as f64 * 0.1, -angle.sin()]);
+                if let Some(rec) = shape.hit(&r, 0.001, f64::INFINITY) {
+                    for axis in 0..3 {
+                        assert!(rec.p[axis] >= bbox.min[axis] - 1e-6 && rec.p[axis] <= bbox.max[axis] + 1e-6);
+                    }
+                    assert!((0.0..=1.0).contains(&rec.u) && (0.0..=1.0).contains(&rec.v));
+                }
+            }
+        }
+    }
+}
@@ -4,11 +4,19 @@ use crate::vec3::{Point3, Vec3};
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    /// Moment within the camera's shutter interval the ray samples; moving
+    /// objects are intersected where they are at this time.
+    pub time: f64,
 }
 
 impl Ray {
+    /// A ray at time zero.
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self { origin, direction, time: 0.0 }
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Self {
+        Self { origin, direction, time }
     }
 
     pub fn at(&self, t: f64) -> Point3 {
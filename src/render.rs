@@ -0,0 +1,513 @@
+use crate::aov::{Aov, AovAccumulator, AovImage};
+use crate::background::Background;
+use crate::camera::Camera;
+use crate::film::{Film, FilmTile};
+use crate::filter::Filter;
+use crate::framebuffer::Framebuffer;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::ray::Ray;
+use crate::sampler::{Sampler, SamplerKind};
+use crate::scene::Scene;
+use crate::tonemap::srgb_to_linear;
+use crate::vec3::Color;
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Upper bound on the camera rays averaged per pixel for the AOV passes.
+const AOV_SAMPLES: u32 = 16;
+
+/// Side length in pixels of the square tiles the image is scheduled in.
+const TILE_SIZE: u32 = 32;
+
+/// Tiles per rayon thread rendered between two merges into the film; bounds
+/// the memory of in-flight tiles and how often [`Renderer::render_into`]
+/// reports back.
+const TILES_PER_THREAD: usize = 4;
+
+/// Sampler dimensions taken by the camera ray: pixel jitter, lens and shutter time.
+const CAMERA_DIMENSIONS: u32 = 5;
+
+/// Sampler dimensions reserved per bounce. The BSDF sample takes the first
+/// [`SCATTER_DIMENSIONS`] and light sampling (picking a light, then a point
+/// on it) the rest; a decision that needs more spills into the next bounce's
+/// dimensions, which only costs stratification.
+const BOUNCE_DIMENSIONS: u32 = 8;
+const SCATTER_DIMENSIONS: u32 = 3;
+
+/// Mixed into the seed of the AOV passes so they don't reuse the beauty pass's samples.
+const AOV_SEED: u64 = 0xA0F5_17E5_5EED_0001;
+
+/// Image size and sampling parameters for one render.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    /// Samples per pixel; the upper bound when sampling adaptively.
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    /// Enables adaptive sampling: a pixel stops receiving samples once the
+    /// relative standard error of its luminance falls below this value.
+    pub adaptive_threshold: Option<f64>,
+    /// Samples every pixel receives before adaptive sampling may stop it.
+    pub min_samples: u32,
+    /// Seeds every random decision; the same seed and settings give the same
+    /// image regardless of thread count.
+    pub seed: u64,
+    /// How sample values are distributed over each pixel.
+    pub sampler: SamplerKind,
+    /// Reconstruction filter weighting samples into nearby pixels.
+    pub filter: Filter,
+    /// Enables progressive rendering: the whole image receives this many more
+    /// samples per pixel in each pass, until `samples_per_pixel` is reached.
+    pub pass_samples: Option<u32>,
+    /// Renders only pixels `x0..x1` × `y0..y1` (from the top-left corner) of
+    /// the full image; everything else stays black. Rays are those of the
+    /// full frame, and a margin as wide as the filter radius is sampled
+    /// around the crop, so crops of one image stitch together without seams.
+    pub crop: Option<[u32; 4]>,
+    /// Stops rendering once this much time has passed, keeping the samples
+    /// taken so far. Tiles not reached are left out of the current pass, so
+    /// this is best combined with `pass_samples`.
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self { width: 400, height: 225, samples_per_pixel: 50, max_depth: 10, adaptive_threshold: None, min_samples: 16, seed: 0, sampler: SamplerKind::Independent, filter: Filter::default(), pass_samples: None, crop: None, time_limit: None }
+    }
+}
+
+impl RenderSettings {
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// The pixels being rendered: the crop, or the whole image.
+    pub fn region(&self) -> [u32; 4] {
+        self.crop.unwrap_or([0, 0, self.width, self.height])
+    }
+
+    /// The pixels that receive samples: the region, grown around a crop by
+    /// the filter radius so the crop's edge pixels get the contributions of
+    /// their neighbours outside it, just as in a full render.
+    pub fn sampled_region(&self) -> [u32; 4] {
+        let Some([x0, y0, x1, y1]) = self.crop else {
+            return [0, 0, self.width, self.height];
+        };
+        let margin = self.filter.radius.ceil() as u32;
+        [x0.saturating_sub(margin), y0.saturating_sub(margin), (x1 + margin).min(self.width), (y1 + margin).min(self.height)]
+    }
+}
+
+/// A rendered image together with the number of samples spent on each pixel.
+#[derive(Clone, Debug)]
+pub struct RenderOutput {
+    pub image: Framebuffer,
+    /// Row-major from the top-left corner, like the image.
+    pub sample_counts: Vec<u32>,
+}
+
+impl RenderOutput {
+    pub fn average_samples(&self) -> f64 {
+        self.sample_counts.iter().map(|&n| n as f64).sum::<f64>() / self.sample_counts.len().max(1) as f64
+    }
+
+    /// Visualizes the sample counts with an inferno-like ramp, black for no
+    /// samples through purple and orange to pale yellow for `max_samples`.
+    pub fn sample_heatmap(&self, max_samples: u32) -> Framebuffer {
+        const RAMP: [[f64; 3]; 5] =
+            [[0.0, 0.0, 4.0], [87.0, 16.0, 110.0], [188.0, 55.0, 84.0], [249.0, 142.0, 9.0], [252.0, 255.0, 164.0]];
+        let mut heatmap = Framebuffer::new(self.image.width, self.image.height);
+        for (pixel, &n) in heatmap.pixels.iter_mut().zip(&self.sample_counts) {
+            let t = (n as f64 / max_samples.max(1) as f64).clamp(0.0, 1.0) * (RAMP.len() - 1) as f64;
+            let i = (t as usize).min(RAMP.len() - 2);
+            let f = t - i as f64;
+            let channel = |c: usize| srgb_to_linear((RAMP[i][c] * (1.0 - f) + RAMP[i + 1][c] * f) / 255.0);
+            *pixel = Color::new(channel(0), channel(1), channel(2));
+        }
+        heatmap
+    }
+}
+
+/// A render in progress: the film and the sampling statistics of every pixel.
+/// Rendering into an existing state continues where it stopped, so a state
+/// restored from a checkpoint can be finished or given more samples per pixel.
+pub struct RenderState {
+    pub(crate) film: Film,
+    /// Row-major from the top-left corner, like the film.
+    pub(crate) stats: Vec<PixelStats>,
+    /// Passes completed over the whole image since the state was created or loaded.
+    pub(crate) passes: u32,
+    /// Pixels kept by [`RenderState::output`]. The film and statistics also
+    /// cover the margin sampled around the crop.
+    pub(crate) crop: Option<[u32; 4]>,
+}
+
+impl RenderState {
+    /// An empty state for `settings`, without any samples.
+    pub fn new(settings: &RenderSettings) -> Self {
+        let n = settings.width as usize * settings.height as usize;
+        Self { film: Film::new(settings.width, settings.height, settings.filter), stats: vec![PixelStats::default(); n], passes: 0, crop: settings.crop }
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Root mean square of the pixels' relative standard errors (see
+    /// [`RenderSettings::adaptive_threshold`]), or infinity before any pixel
+    /// has two samples.
+    pub fn noise_estimate(&self) -> f64 {
+        let (sum, n) = self
+            .stats
+            .iter()
+            .map(PixelStats::relative_error)
+            .filter(|e| e.is_finite())
+            .fold((0.0, 0), |(sum, n), e| (sum + e * e, n + 1));
+        if n == 0 { f64::INFINITY } else { (sum / n as f64).sqrt() }
+    }
+
+    /// Adds a tile rendered by [`Renderer::render_tile`] for the pixels `rect`.
+    pub(crate) fn merge_tile(&mut self, [x0, y0, x1, y1]: [u32; 4], tile: &FilmTile, stats: Vec<PixelStats>) {
+        self.film.merge(tile);
+        let mut stats = stats.into_iter();
+        for y in y0..y1 {
+            for x in x0..x1 {
+                self.stats[(y * self.film.width + x) as usize] = stats.next().unwrap();
+            }
+        }
+    }
+
+    /// The image and sample counts; outside a crop both are zero.
+    pub fn output(&self) -> RenderOutput {
+        let mut output = RenderOutput { image: self.film.image(), sample_counts: self.stats.iter().map(|s| s.count).collect() };
+        if let Some([x0, y0, x1, y1]) = self.crop {
+            let width = self.film.width;
+            for (i, (pixel, count)) in output.image.pixels.iter_mut().zip(&mut output.sample_counts).enumerate() {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                if !(x0..x1).contains(&x) || !(y0..y1).contains(&y) {
+                    *pixel = Color::zero();
+                    *count = 0;
+                }
+            }
+        }
+        output
+    }
+}
+
+/// Running luminance statistics of one pixel (Welford's algorithm).
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PixelStats {
+    pub(crate) count: u32,
+    pub(crate) mean: f64,
+    pub(crate) m2: f64,
+}
+
+impl PixelStats {
+    fn add(&mut self, sample: Color) {
+        self.count += 1;
+        let l = sample.luminance();
+        let delta = l - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (l - self.mean);
+    }
+
+    /// Standard error of the mean luminance relative to the mean itself.
+    /// Very dark pixels are measured against a floor so they can converge.
+    fn relative_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        (variance / self.count as f64).sqrt() / self.mean.max(0.01)
+    }
+}
+
+/// Renders scenes into floating-point framebuffers, tiles in parallel.
+pub struct Renderer {
+    pub settings: RenderSettings,
+}
+
+impl Renderer {
+    pub fn new(settings: RenderSettings) -> Self {
+        Self { settings }
+    }
+
+    pub fn render(&self, scene: &Scene) -> Framebuffer {
+        self.render_with_progress(scene, || {}).image
+    }
+
+    /// Like [`Renderer::render`], calling `on_tile` each time one of the
+    /// [`Renderer::tile_count`] tiles finishes.
+    pub fn render_with_progress(&self, scene: &Scene, on_tile: impl Fn() + Sync) -> RenderOutput {
+        let mut state = RenderState::new(&self.settings);
+        self.render_into(scene, &mut state, on_tile, |_| {});
+        state.output()
+    }
+
+    /// Number of tiles the image is split into.
+    pub fn tile_count(&self) -> usize {
+        self.tiles().len()
+    }
+
+    /// Tops up every pixel of `state` to `samples_per_pixel` samples, or until
+    /// adaptive sampling finds it converged. Pixels that are already done are
+    /// skipped, so this both resumes interrupted renders and refines finished
+    /// ones. With [`RenderSettings::pass_samples`] the image is covered in
+    /// several passes, otherwise in one; rendering ends early once
+    /// [`RenderSettings::time_limit`] runs out.
+    ///
+    /// Tiles render in parallel in batches; `on_tile` is called as each tile
+    /// finishes and `on_batch` after each batch is merged into `state`, e.g.
+    /// to write a checkpoint or a snapshot once [`RenderState::passes`] grows.
+    pub fn render_into(
+        &self,
+        scene: &Scene,
+        state: &mut RenderState,
+        on_tile: impl Fn() + Sync,
+        mut on_batch: impl FnMut(&RenderState),
+    ) {
+        let RenderSettings { width, samples_per_pixel, pass_samples, time_limit, .. } = self.settings;
+        let deadline = time_limit.map(|limit| Instant::now() + limit);
+        let expired = || deadline.is_some_and(|d| Instant::now() >= d);
+        let cam = &scene.camera.build(self.settings.aspect_ratio());
+        let tiles = self.tiles();
+        let batch_size = rayon::current_num_threads() * TILES_PER_THREAD;
+
+        // Skip the passes a resumed state has already covered.
+        let step = pass_samples.unwrap_or(samples_per_pixel).max(1);
+        let [x0, y0, x1, y1] = self.settings.sampled_region();
+        let region_pixels = (y0..y1).flat_map(|y| (x0..x1).map(move |x| (y * width + x) as usize));
+        let done = region_pixels.map(|i| state.stats[i].count).min().unwrap_or(0);
+        let mut target = done - done % step;
+
+        while target < samples_per_pixel {
+            target = target.saturating_add(step).min(samples_per_pixel);
+            for (b, batch) in tiles.chunks(batch_size).enumerate() {
+                let state_ref = &*state;
+                let rendered: Vec<Option<(FilmTile, Vec<PixelStats>)>> = batch
+                    .par_iter()
+                    .map(|&rect| {
+                        if expired() {
+                            return None;
+                        }
+                        let rendered = self.render_tile(scene, cam, state_ref, rect, target);
+                        on_tile();
+                        Some(rendered)
+                    })
+                    .collect();
+
+                // Merge in tile order so the result doesn't depend on scheduling.
+                let complete = rendered.iter().all(Option::is_some);
+                for (&rect, (tile, stats)) in batch.iter().zip(rendered).filter_map(|(t, r)| Some((t, r?))) {
+                    state.merge_tile(rect, &tile, stats);
+                }
+                if complete && (b + 1) * batch_size >= tiles.len() {
+                    state.passes += 1;
+                }
+                on_batch(state);
+                if !complete {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Continues the pixels `rect` of `state` up to `target` samples each,
+    /// returning the new film tile and pixel statistics for
+    /// [`RenderState::merge_tile`]; `state` itself is left untouched.
+    pub(crate) fn render_tile(
+        &self,
+        scene: &Scene,
+        cam: &Camera,
+        state: &RenderState,
+        [x0, y0, x1, y1]: [u32; 4],
+        target: u32,
+    ) -> (FilmTile, Vec<PixelStats>) {
+        let width = self.settings.width;
+        let mut tile = state.film.tile(x0, y0, x1, y1);
+        let mut stats = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let mut pixel = state.stats[(y * width + x) as usize].clone();
+                self.sample_pixel(scene, cam, (x, y), target, &mut pixel, &mut tile);
+                stats.push(pixel);
+            }
+        }
+        (tile, stats)
+    }
+
+    /// Pixel rectangles `[x0, y0, x1, y1]` of at most [`TILE_SIZE`] squared
+    /// covering [`RenderSettings::sampled_region`], row-major from the
+    /// top-left corner. Tiles follow the full image's grid, clipped to the
+    /// region, so a crop's pixels are summed in the same order as in a full
+    /// render and stitched crops match it exactly.
+    pub(crate) fn tiles(&self) -> Vec<[u32; 4]> {
+        let [rx0, ry0, rx1, ry1] = self.settings.sampled_region();
+        let mut tiles = Vec::new();
+        for y0 in (ry0 - ry0 % TILE_SIZE..ry1).step_by(TILE_SIZE as usize) {
+            for x0 in (rx0 - rx0 % TILE_SIZE..rx1).step_by(TILE_SIZE as usize) {
+                tiles.push([x0.max(rx0), y0.max(ry0), (x0 + TILE_SIZE).min(rx1), (y0 + TILE_SIZE).min(ry1)]);
+            }
+        }
+        tiles
+    }
+
+    /// Continues sampling pixel (`x`, `y`), counting rows from the top, until
+    /// it has `target` samples or adaptive sampling finds it converged.
+    /// Samples are splatted into `tile` and recorded in `stats`.
+    fn sample_pixel(&self, scene: &Scene, cam: &Camera, (x, y): (u32, u32), target: u32, stats: &mut PixelStats, tile: &mut FilmTile) {
+        let RenderSettings { width, height, samples_per_pixel, max_depth, adaptive_threshold, min_samples, seed, sampler, .. } =
+            self.settings;
+        let i = x;
+        let j = height - 1 - y; // the camera counts scanlines bottom->top
+        let mut sampler = sampler.create(seed, samples_per_pixel);
+        for s in stats.count..target {
+            let converged = adaptive_threshold.is_some_and(|t| s >= min_samples && stats.relative_error() < t);
+            if converged {
+                break;
+            }
+
+            sampler.start_pixel_sample(i, j, s);
+            let [du, dv] = sampler.get_2d();
+            let u = (i as f64 + du) / (width as f64 - 1.0);
+            let v = (j as f64 + dv) / (height as f64 - 1.0);
+            let r = cam.get_ray(u, v, sampler.as_mut());
+            let color = ray_color(&r, &scene.world, &scene.lights, &scene.background, max_depth, sampler.as_mut());
+            // Image rows grow downwards while `dv` moves up the scanline.
+            tile.add_sample(i as f64 + du, (height - j) as f64 - dv, color);
+            stats.add(color);
+        }
+    }
+
+    /// Renders the requested AOV passes from primary hits only. Up to
+    /// [`AOV_SAMPLES`] jittered camera rays per pixel are averaged so edges are
+    /// antialiased like the beauty pass; the ID passes use the ray through the
+    /// pixel center instead, since IDs cannot be blended.
+    pub fn render_aovs(&self, scene: &Scene, aovs: &[Aov]) -> Vec<AovImage> {
+        if aovs.is_empty() {
+            return Vec::new();
+        }
+        let RenderSettings { width, height, samples_per_pixel, seed, sampler, .. } = self.settings;
+        let cam = &scene.camera.build(self.settings.aspect_ratio());
+        let samples = samples_per_pixel.clamp(1, AOV_SAMPLES);
+        let [x0, y0, x1, y1] = self.settings.region();
+
+        let pixels: Vec<AovAccumulator> = (0..height).into_par_iter().flat_map_iter(|y| {
+            let j = height - 1 - y; // camera v runs bottom->top
+            (0..width).map(move |i| {
+                // Pixels outside the crop read as misses.
+                if !(x0..x1).contains(&i) || !(y0..y1).contains(&y) {
+                    return AovAccumulator::default();
+                }
+                let mut sampler = sampler.create(seed ^ AOV_SEED, samples + 1);
+                let mut acc = AovAccumulator::default();
+                for s in 0..samples {
+                    sampler.start_pixel_sample(i, j, s);
+                    let [du, dv] = sampler.get_2d();
+                    let u = (i as f64 + du) / (width as f64 - 1.0);
+                    let v = (j as f64 + dv) / (height as f64 - 1.0);
+                    acc.add(&cam.get_ray(u, v, sampler.as_mut()), scene);
+                }
+                sampler.start_pixel_sample(i, j, samples);
+                let center = cam.get_ray((i as f64 + 0.5) / (width as f64 - 1.0), (j as f64 + 0.5) / (height as f64 - 1.0), sampler.as_mut());
+                acc.set_ids(&center, scene);
+                acc
+            }).collect::<Vec<_>>()
+        }).collect();
+
+        aovs.iter()
+            .map(|&aov| {
+                let mut image = Framebuffer::new(width, height);
+                image.pixels = pixels.iter().map(|p| p.value(aov)).collect();
+                AovImage { aov, image }
+            })
+            .collect()
+    }
+}
+
+/// Path-traces `r`. At every non-specular bounce one point on `lights` is
+/// sampled explicitly (next-event estimation); emission found by following the
+/// BSDF sample is then weighted against it with the power heuristic (MIS).
+pub fn ray_color(
+    r: &Ray,
+    world: &dyn Hittable,
+    lights: &HittableList,
+    background: &Background,
+    max_depth: u32,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let mut radiance = Color::zero();
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut ray = *r;
+    // Density with which the BSDF chose `ray`, if light sampling could also have produced it.
+    let mut bsdf_pdf: Option<f64> = None;
+
+    for bounce in 0..max_depth {
+        let dimension = CAMERA_DIMENSIONS + bounce * BOUNCE_DIMENSIONS;
+        let Some(rec) = world.hit(&ray, 0.001, f64::INFINITY) else {
+            radiance += throughput * background.color(&ray);
+            break;
+        };
+
+        let emitted = rec.mat.emitted(&ray, &rec);
+        if emitted != Color::zero() {
+            let weight = match bsdf_pdf {
+                Some(pdf) => power_heuristic(pdf, lights.pdf_value(&ray.origin, &ray.direction)),
+                None => 1.0,
+            };
+            radiance += throughput * emitted * weight;
+        }
+
+        sampler.set_dimension(dimension);
+        let Some((attenuation, scattered)) = rec.mat.scatter(&ray, &rec, sampler) else {
+            break;
+        };
+
+        // Skip light sampling on the last bounce: its BSDF-sampled counterpart is never traced.
+        let pdf = rec.mat.scattering_pdf(&ray, &rec, &scattered);
+        if pdf > 0.0 && !lights.objects.is_empty() && bounce + 1 < max_depth {
+            sampler.set_dimension(dimension + SCATTER_DIMENSIONS);
+            radiance += throughput * sample_light(&ray, &rec, world, lights, sampler);
+            bsdf_pdf = Some(pdf);
+        } else {
+            bsdf_pdf = None;
+        }
+
+        throughput = throughput * attenuation;
+        ray = scattered;
+    }
+
+    radiance
+}
+
+/// Direct light reaching `rec` from one point sampled on `lights`, MIS-weighted against BSDF sampling.
+fn sample_light(r_in: &Ray, rec: &HitRecord, world: &dyn Hittable, lights: &HittableList, sampler: &mut dyn Sampler) -> Color {
+    let to_light = Ray::with_time(rec.p, lights.random(&rec.p, sampler), r_in.time);
+    let light_pdf = lights.pdf_value(&to_light.origin, &to_light.direction);
+    if light_pdf <= 0.0 {
+        return Color::zero();
+    }
+
+    let f = rec.mat.eval(r_in, rec, &to_light);
+    if f == Color::zero() {
+        return Color::zero();
+    }
+
+    // The first surface along the sample is what we actually see, light or occluder.
+    let Some(light_rec) = world.hit(&to_light, 0.001, f64::INFINITY) else {
+        return Color::zero();
+    };
+    let emitted = light_rec.mat.emitted(&to_light, &light_rec);
+    let weight = power_heuristic(light_pdf, rec.mat.scattering_pdf(r_in, rec, &to_light));
+    f * emitted * (weight / light_pdf)
+}
+
+/// Power heuristic (β = 2) weight for a sample drawn with density `pdf_a`
+/// that another strategy could have drawn with density `pdf_b`.
+fn power_heuristic(pdf_a: f64, pdf_b: f64) -> f64 {
+    let a = pdf_a * pdf_a;
+    let b = pdf_b * pdf_b;
+    if a + b == 0.0 { 0.0 } else { a / (a + b) }
+}
+
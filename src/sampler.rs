@@ -0,0 +1,675 @@
+//! Sample generators for the Monte Carlo integrator.
+//!
+//! Every random decision of a path (pixel jitter, lens position, BSDF and
+//! light sampling) asks a [`Sampler`] for the next one or two *dimensions* of
+//! the current pixel sample. The samplers differ in how well the values of a
+//! dimension are spread over the samples of a pixel:
+//!
+//! - `independent`: uniform random numbers;
+//! - `stratified`: one jittered sample per stratum, strata visited in a random order;
+//! - `halton`: the Halton sequence, Owen-scrambled per pixel so high dimensions stay uncorrelated;
+//! - `sobol`: the Sobol sequence with hash-based Owen scrambling (Burley 2020),
+//!   padded from 2D so that every pair of dimensions is well distributed;
+//! - `blue-noise`: a Sobol sequence shared by all pixels and offset per pixel by a
+//!   blue-noise mask, so the remaining error looks like fine grain instead of blotches.
+//!
+//! All samplers are deterministic functions of the seed, pixel and sample index.
+//!
+//! The integrator moves to a fixed dimension before each decision with
+//! [`Sampler::set_dimension`], so e.g. the BSDF sample of the second bounce
+//! reads the same dimensions in every sample of a pixel, whatever the
+//! earlier bounces drew. Without that the low-discrepancy samplers would
+//! stratify values of unrelated decisions against each other.
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+pub trait Sampler: Send {
+    /// Starts sample `index` of pixel (`x`, `y`); dimensions restart from the first.
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32);
+
+    /// The next dimension of the current sample, in [0, 1).
+    fn get_1d(&mut self) -> f64;
+
+    /// The next two dimensions of the current sample, in [0, 1)².
+    fn get_2d(&mut self) -> [f64; 2];
+
+    /// Continues the current sample at `dimension`.
+    fn set_dimension(&mut self, dimension: u32);
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    #[default]
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub const ALL: [SamplerKind; 5] =
+        [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol, SamplerKind::BlueNoise];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+            SamplerKind::BlueNoise => "blue-noise",
+        }
+    }
+
+    /// A sampler for pixels taking up to `samples_per_pixel` samples. Only the
+    /// stratified sampler needs the count; the sequences work for any prefix.
+    pub fn create(self, seed: u64, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(seed, samples_per_pixel)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+impl fmt::Display for SamplerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SamplerKind::ALL.into_iter().find(|k| k.name() == s).ok_or_else(|| {
+            let names: Vec<&str> = SamplerKind::ALL.iter().map(|k| k.name()).collect();
+            format!("unknown sampler '{}' (expected one of: {})", s, names.join(", "))
+        })
+    }
+}
+
+/// Pixel, sample index and next dimension of the sample being generated.
+#[derive(Clone, Debug, Default)]
+struct SampleState {
+    x: u32,
+    y: u32,
+    index: u32,
+    dimension: u32,
+}
+
+impl SampleState {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        *self = Self { x, y, index, dimension: 0 };
+    }
+
+    /// Claims `count` dimensions and returns the first.
+    fn take(&mut self, count: u32) -> u32 {
+        let d = self.dimension;
+        self.dimension += count;
+        d
+    }
+
+    /// A hash of the seed, pixel and dimension, constant over the pixel's samples.
+    fn pixel_hash(&self, seed: u64, dimension: u32) -> u64 {
+        hash(&[seed, self.x as u64, self.y as u64, dimension as u64])
+    }
+
+    /// Claims `count` dimensions and returns the pixel hash of the first.
+    fn next_hash(&mut self, seed: u64, count: u32) -> u64 {
+        let d = self.take(count);
+        self.pixel_hash(seed, d)
+    }
+}
+
+/// Uniform random numbers, from a generator seeded per pixel sample.
+pub struct IndependentSampler {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, rng: SmallRng::seed_from_u64(seed) }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.rng = SmallRng::seed_from_u64(hash(&[self.seed, x as u64, y as u64, index as u64]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.r#gen()
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        [self.rng.r#gen(), self.rng.r#gen()]
+    }
+
+    /// Every dimension is alike, so the stream simply continues.
+    fn set_dimension(&mut self, _dimension: u32) {}
+}
+
+/// Jittered stratification: with `n` samples per pixel each 1D dimension is
+/// split into `n` strata and each 2D dimension into a roughly square grid of
+/// at least `n` cells. Sample `i` takes the `i`-th cell of a permutation that
+/// differs per pixel and dimension, so dimensions stay uncorrelated.
+/// Beyond [`MAX_STRATA`] samples (say, a time-limited render without a sample
+/// count) the strata stop growing.
+pub struct StratifiedSampler {
+    seed: u64,
+    samples: u32,
+    state: SampleState,
+}
+
+/// Upper bound on the strata per dimension, keeping the 2D grid within `u32`.
+const MAX_STRATA: u32 = 1 << 16;
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: u32) -> Self {
+        Self { seed, samples: samples_per_pixel.clamp(1, MAX_STRATA), state: SampleState::default() }
+    }
+
+    fn jitter(h: u64, index: u32, axis: u64) -> f64 {
+        to_unit(hash(&[h, index as u64, axis]))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.state.next_hash(self.seed, 1);
+        let n = self.samples;
+        let stratum = permutation_element(self.state.index % n, n, h as u32);
+        (stratum as f64 + Self::jitter(h, self.state.index, 0)) / n as f64
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        let h = self.state.next_hash(self.seed, 2);
+        let nx = ((self.samples as f64).sqrt().round() as u32).max(1);
+        let ny = self.samples.div_ceil(nx);
+        let cell = permutation_element(self.state.index % (nx * ny), nx * ny, h as u32);
+        [
+            ((cell % nx) as f64 + Self::jitter(h, self.state.index, 0)) / nx as f64,
+            ((cell / nx) as f64 + Self::jitter(h, self.state.index, 1)) / ny as f64,
+        ]
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.state.dimension = dimension;
+    }
+}
+
+/// Bases of the Halton dimensions; later dimensions fall back to random numbers.
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107,
+    109, 113, 127, 131,
+];
+
+/// The Halton sequence, dimension `d` being the radical inverse in the `d`-th
+/// prime base. Digits are Owen-scrambled per pixel and dimension; a mere
+/// random offset would keep the strong correlation between neighbouring
+/// large bases, which makes later bounces worse than independent sampling.
+pub struct HaltonSampler {
+    seed: u64,
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: SampleState::default() }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let d = self.state.take(1);
+        let h = self.state.pixel_hash(self.seed, d);
+        match PRIMES.get(d as usize) {
+            Some(&base) => radical_inverse(base, self.state.index, Some(h)),
+            None => to_unit(hash(&[h, self.state.index as u64])),
+        }
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        [self.get_1d(), self.get_1d()]
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.state.dimension = dimension;
+    }
+}
+
+/// The radical inverse of `index` in `base`. With a `scramble` hash each
+/// digit is permuted depending on the digits before it (Owen scrambling),
+/// and digits continue past the index's own ones to full precision.
+fn radical_inverse(base: u32, mut index: u32, scramble: Option<u64>) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_n = 1.0;
+    let mut reversed = 0u64;
+    while 1.0 - (base - 1) as f64 * inv_base_n < 1.0 && (index > 0 || scramble.is_some()) {
+        let next = index / base;
+        let mut digit = index - next * base;
+        if let Some(h) = scramble {
+            digit = permutation_element(digit, base, mix64(h ^ reversed) as u32);
+        }
+        reversed = reversed * base as u64 + digit as u64;
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    (reversed as f64 * inv_base_n).min(ONE_MINUS_EPSILON)
+}
+
+/// Owen-scrambled Sobol points. Each 1D or 2D request uses the first one or
+/// two Sobol dimensions with its own scrambling seed and index shuffle
+/// (padding), which keeps every pair well stratified without needing
+/// direction numbers for hundreds of dimensions.
+pub struct SobolSampler {
+    seed: u64,
+    state: SampleState,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: SampleState::default() }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.state.next_hash(self.seed, 1);
+        shuffled_scrambled_sobol_1d(self.state.index, h)
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        let h = self.state.next_hash(self.seed, 2);
+        shuffled_scrambled_sobol_2d(self.state.index, h)
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.state.dimension = dimension;
+    }
+}
+
+/// Blue-noise dithered sampling (Georgiev & Fajardo 2016): every pixel uses
+/// the same scrambled Sobol points, rotated by the value of a 64×64 blue-noise
+/// mask under the pixel. Neighbouring pixels get very different offsets, so
+/// their errors are decorrelated in a visually pleasant way. The mask is
+/// shifted per dimension.
+pub struct BlueNoiseSampler {
+    seed: u64,
+    state: SampleState,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: SampleState::default() }
+    }
+
+    /// Mask value under the current pixel, shifted by a hash of (`dimension`, `axis`).
+    fn offset(&self, dimension: u32, axis: u64) -> f64 {
+        let shift = hash(&[self.seed, dimension as u64, axis]);
+        let x = (self.state.x as usize + (shift as usize % MASK_SIZE)) % MASK_SIZE;
+        let y = (self.state.y as usize + ((shift >> 32) as usize % MASK_SIZE)) % MASK_SIZE;
+        blue_noise_mask()[y * MASK_SIZE + x]
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let d = self.state.take(1);
+        let point = shuffled_scrambled_sobol_1d(self.state.index, hash(&[self.seed, d as u64]));
+        (point + self.offset(d, 0)).fract()
+    }
+
+    fn get_2d(&mut self) -> [f64; 2] {
+        let d = self.state.take(2);
+        let [u, v] = shuffled_scrambled_sobol_2d(self.state.index, hash(&[self.seed, d as u64]));
+        [(u + self.offset(d, 0)).fract(), (v + self.offset(d, 1)).fract()]
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.state.dimension = dimension;
+    }
+}
+
+/// Largest f64 below 1.
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+fn to_unit(h: u64) -> f64 {
+    (h >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+fn bits_to_unit(x: u32) -> f64 {
+    x as f64 * (1.0 / (1u64 << 32) as f64)
+}
+
+/// SplitMix64 finalizer: a cheap bijective hash with good avalanche.
+fn mix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |h, &v| mix64(h ^ v))
+}
+
+/// Element `i` of a pseudo-random permutation of `0..n` selected by `p`,
+/// without storing the permutation (Kensler 2013).
+fn permutation_element(mut i: u32, n: u32, p: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | (p >> 27));
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % n
+}
+
+/// Hash that only lets each bit depend on the bits below it, so on
+/// bit-reversed values it acts as an Owen scramble (Laine–Karras, with the
+/// improved constants by Vegdahl).
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x ^= x.wrapping_mul(0x3d20adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x05526c56);
+    x ^= x.wrapping_mul(0x53a22864);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Second Sobol dimension; the first is simply the bit-reversed index.
+fn sobol_dimension_1(index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1u32 << 31;
+    let mut index = index;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+fn shuffled_scrambled_sobol_1d(index: u32, h: u64) -> f64 {
+    let index = nested_uniform_scramble(index, h as u32);
+    bits_to_unit(nested_uniform_scramble(index.reverse_bits(), (h >> 32) as u32))
+}
+
+fn shuffled_scrambled_sobol_2d(index: u32, h: u64) -> [f64; 2] {
+    let index = nested_uniform_scramble(index, h as u32);
+    let seeds = mix64(h);
+    [
+        bits_to_unit(nested_uniform_scramble(index.reverse_bits(), seeds as u32)),
+        bits_to_unit(nested_uniform_scramble(sobol_dimension_1(index), (seeds >> 32) as u32)),
+    ]
+}
+
+const MASK_SIZE: usize = 64;
+
+fn blue_noise_mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+/// Builds a tileable blue-noise threshold mask with Ulichney's void-and-cluster
+/// method: pixels are ranked by repeatedly filling the largest void (or
+/// removing the tightest cluster) of a binary pattern, measured by a toroidal
+/// Gaussian energy. Returns the ranks scaled to [0, 1), one per pixel.
+fn void_and_cluster() -> Vec<f64> {
+    const SIGMA: f64 = 1.5;
+    let n = MASK_SIZE * MASK_SIZE;
+    let kernel: Vec<f64> = (0..n)
+        .map(|i| {
+            let dx = (i % MASK_SIZE).min(MASK_SIZE - i % MASK_SIZE) as f64;
+            let dy = (i / MASK_SIZE).min(MASK_SIZE - i / MASK_SIZE) as f64;
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let splat = |energy: &mut [f64], p: usize, sign: f64| {
+        let (px, py) = (p % MASK_SIZE, p / MASK_SIZE);
+        for (q, e) in energy.iter_mut().enumerate() {
+            let dx = (q % MASK_SIZE + MASK_SIZE - px) % MASK_SIZE;
+            let dy = (q / MASK_SIZE + MASK_SIZE - py) % MASK_SIZE;
+            *e += sign * kernel[dy * MASK_SIZE + dx];
+        }
+    };
+    // Extreme energy among the pixels whose pattern value is `set`.
+    let tightest = |pattern: &[bool], energy: &[f64], set: bool| {
+        (0..n).filter(|&i| pattern[i] == set).max_by(|&a, &b| energy[a].total_cmp(&energy[b])).expect("pixel available")
+    };
+    let loosest = |pattern: &[bool], energy: &[f64], set: bool| {
+        (0..n).filter(|&i| pattern[i] == set).min_by(|&a, &b| energy[a].total_cmp(&energy[b])).expect("pixel available")
+    };
+
+    // Initial pattern: 10% random points, relaxed until no point moves.
+    let initial = n / 10;
+    let mut rng = SmallRng::seed_from_u64(0x5EED);
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0; n];
+    let mut placed = 0;
+    while placed < initial {
+        let p = rng.gen_range(0..n);
+        if !pattern[p] {
+            pattern[p] = true;
+            splat(&mut energy, p, 1.0);
+            placed += 1;
+        }
+    }
+    loop {
+        let cluster = tightest(&pattern, &energy, true);
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.0);
+        let void = loosest(&pattern, &energy, false);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0usize; n];
+    // Phase 1: rank the initial points by removing the tightest clusters first.
+    {
+        let mut pattern = pattern.clone();
+        let mut energy = energy.clone();
+        for r in (0..initial).rev() {
+            let cluster = tightest(&pattern, &energy, true);
+            pattern[cluster] = false;
+            splat(&mut energy, cluster, -1.0);
+            rank[cluster] = r;
+        }
+    }
+    // Phase 2: fill the largest voids up to half the pixels.
+    for r in initial..n / 2 {
+        let void = loosest(&pattern, &energy, false);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+    // Phase 3: the empty pixels are now the minority; fill their tightest clusters.
+    let mut energy = vec![0.0; n];
+    for p in (0..n).filter(|&p| !pattern[p]) {
+        splat(&mut energy, p, 1.0);
+    }
+    for r in n / 2..n {
+        let cluster = tightest(&pattern, &energy, false);
+        pattern[cluster] = true;
+        splat(&mut energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+
+    rank.into_iter().map(|r| (r as f64 + 0.5) / n as f64).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-12, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn halton_radical_inverses_match_reference() {
+        let base_2 = [0.0, 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875];
+        let base_3 = [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0 / 9.0, 4.0 / 9.0, 7.0 / 9.0, 2.0 / 9.0, 5.0 / 9.0, 8.0 / 9.0];
+        for (i, &expected) in base_2.iter().enumerate() {
+            assert_close(radical_inverse(2, i as u32, None), expected);
+        }
+        for (i, &expected) in base_3.iter().enumerate() {
+            assert_close(radical_inverse(3, i as u32, None), expected);
+        }
+    }
+
+    #[test]
+    fn sobol_points_match_reference() {
+        let first = [0.0, 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875];
+        let second = [0.0, 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875];
+        for i in 0..8 {
+            assert_close(bits_to_unit((i as u32).reverse_bits()), first[i]);
+            assert_close(bits_to_unit(sobol_dimension_1(i as u32)), second[i]);
+        }
+    }
+
+    /// Owen scrambling keeps the first 2^m points a (0, m, 2)-net: every
+    /// elementary interval of area 2^-m holds exactly one point.
+    #[test]
+    fn scrambled_sobol_is_a_net() {
+        const M: u32 = 8;
+        for h in [1, 0xDEAD_BEEF, u64::MAX / 3] {
+            let points: Vec<[f64; 2]> = (0..1 << M).map(|i| shuffled_scrambled_sobol_2d(i, h)).collect();
+            for a in 0..=M {
+                let (nx, ny) = (1usize << a, 1usize << (M - a));
+                let mut cells = vec![0; nx * ny];
+                for [u, v] in &points {
+                    cells[(v * ny as f64) as usize * nx + (u * nx as f64) as usize] += 1;
+                }
+                assert!(cells.iter().all(|&c| c == 1), "{}×{} intervals not stratified", nx, ny);
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_sampler_covers_every_stratum() {
+        for samples in [16, 10] {
+            let mut sampler = StratifiedSampler::new(7, samples);
+            let nx = ((samples as f64).sqrt().round() as usize).max(1);
+            let ny = (samples as usize).div_ceil(nx);
+            let mut strata = vec![0; samples as usize];
+            let mut cells = vec![0; nx * ny];
+            for s in 0..samples {
+                sampler.start_pixel_sample(3, 5, s);
+                strata[(sampler.get_1d() * samples as f64) as usize] += 1;
+                let [u, v] = sampler.get_2d();
+                cells[(v * ny as f64) as usize * nx + (u * nx as f64) as usize] += 1;
+            }
+            assert!(strata.iter().all(|&c| c == 1), "1D strata {:?}", strata);
+            assert!(cells.iter().all(|&c| c <= 1), "2D cells {:?}", cells);
+            assert_eq!(cells.iter().sum::<i32>(), samples as i32);
+        }
+    }
+
+    #[test]
+    fn set_dimension_ignores_earlier_draws() {
+        for kind in SamplerKind::ALL.into_iter().filter(|&k| k != SamplerKind::Independent) {
+            let mut sampler = kind.create(11, 16);
+            sampler.start_pixel_sample(2, 9, 4);
+            sampler.set_dimension(13);
+            let expected = sampler.get_2d();
+            sampler.start_pixel_sample(2, 9, 4);
+            sampler.get_1d();
+            sampler.get_2d();
+            sampler.set_dimension(13);
+            assert_eq!(sampler.get_2d(), expected, "{}", kind);
+        }
+    }
+
+    /// Root mean square error, over many pixels, of estimating the area of
+    /// the quarter disk with `samples` points read at `dimension`.
+    fn quarter_disk_rmse(kind: SamplerKind, samples: u32, dimension: u32) -> f64 {
+        const PIXELS: u32 = 256;
+        let mut sampler = kind.create(3, samples);
+        let mut squared_error = 0.0;
+        for pixel in 0..PIXELS {
+            let mut inside = 0;
+            for s in 0..samples {
+                sampler.start_pixel_sample(pixel % 16, pixel / 16, s);
+                sampler.set_dimension(dimension);
+                let [u, v] = sampler.get_2d();
+                if u * u + v * v < 1.0 {
+                    inside += 1;
+                }
+            }
+            let error = inside as f64 / samples as f64 - std::f64::consts::FRAC_PI_4;
+            squared_error += error * error;
+        }
+        (squared_error / PIXELS as f64).sqrt()
+    }
+
+    #[test]
+    fn stratified_samplers_converge_faster_than_independent() {
+        // The pixel jitter, and the second bounce's BSDF sample, where Halton's
+        // bases 43 and 47 only stratify coarsely at 64 samples.
+        for (dimension, bound) in [(0, 0.5), (13, 0.8)] {
+            let independent = quarter_disk_rmse(SamplerKind::Independent, 64, dimension);
+            for kind in SamplerKind::ALL.into_iter().filter(|&k| k != SamplerKind::Independent) {
+                let rmse = quarter_disk_rmse(kind, 64, dimension);
+                assert!(rmse < bound * independent, "{} at dimension {}: RMSE {} vs {} independent", kind, dimension, rmse, independent);
+            }
+        }
+    }
+}
@@ -0,0 +1,951 @@
+//! Declarative scene files.
+//!
+//! A scene file is a TOML document with optional `[camera]`, `[render]` and
+//! `[background]` tables, any number of named `[[texture]]` and `[[material]]`
+//! tables and the `[[object]]`s that reference them:
+//!
+//! ```toml
+//! [camera]
+//! lookfrom = [3.0, 3.0, 2.0]
+//! lookat = [0.0, 0.0, -1.0]
+//! vfov = 20.0
+//!
+//! [render]
+//! width = 800
+//! samples = 100
+//!
+//! [[material]]
+//! name = "gold"
+//! type = "metal"
+//! albedo = [0.8, 0.6, 0.2]
+//!
+//! [[object]]
+//! type = "sphere"
+//! center = [1.0, 0.0, -1.0]
+//! radius = 0.5
+//! material = "gold"
+//! ```
+//!
+//! Texture types are `solid` (`color`), `checker` (`scale`, `even`, `odd`),
+//! `image` (`file`) and the Perlin-noise `noise`, `turbulence` and `marble`
+//! (`scale`). Material types are `lambertian` (`albedo`), `metal` (`albedo`,
+//! `fuzz`), `dielectric` (`ir`) and `diffuse_light` (`emit`); colors given to
+//! checkers and materials may be an `[r, g, b]` array or the name of a texture
+//! defined earlier in the file. The background is either
+//! a solid `color` or a `horizon`/`zenith` gradient (the default sky).
+//!
+//! Object types are `sphere` (`center`, `radius`), the infinite `plane`
+//! (`point`, `normal`), `quad` (a parallelogram with a `corner` and edges `u`
+//! and `v`, facing `u × v`), `disk` (`center`, `normal`, `radius`), the
+//! axis-aligned `box` (`min`, `max`), the upright `cylinder`, `cone` and
+//! `paraboloid` (`base`, `radius`, `height`, optional `capped`; cylinders and
+//! cones are capped by default, paraboloids open), `torus` (`center`,
+//! `major_radius`, `minor_radius`, lying in the xz plane), `triangle` (`vertices`,
+//! optional per-vertex `normals` and `uvs`) and `mesh` (`positions`, `indices`,
+//! optional `normals` and `uvs`) and `obj` (`file`, a Wavefront OBJ path
+//! relative to the scene file, optional `groups` to import and an optional
+//! `material` overriding the MTL materials). Emissive quads, disks, boxes,
+//! spheres and triangles are sampled as lights; planes and the curved
+//! primitives other than spheres are not. See
+//! `scenes/default.toml` for the built-in scene.
+//!
+//! Any object can be placed with `scale` (a number or per-axis `[x, y, z]`),
+//! `rotate` (`[x, y, z]` degrees, applied about x, then y, then z) and
+//! `translate`, applied in that order. To place many copies of the same
+//! geometry, describe it once in a named `[[prototype]]` table (same keys as
+//! an object) and add objects of type `instance` with `prototype = "<name>"`
+//! and their own transform; the copies share the prototype's triangles.
+//! Objects of type `scatter` place `count` copies of a `prototype` at random
+//! between the corners `min` and `max`, each turned about y and scaled by a
+//! factor from `scale_range = [low, high]`; `seed` varies the arrangement.
+//! See `scenes/forest.toml`.
+//!
+//! Motion blur needs the `[camera]` shutter to stay open a while:
+//! `shutter_open` and `shutter_close` give the interval sample times are
+//! spread over. Objects of type `moving_sphere` travel from `center0` at
+//! `time0` (default 0) to `center1` at `time1` (default 1), resting at
+//! either end outside that interval. Any other
+//! non-instance object can be moved by keyframes instead of a fixed
+//! transform: `key_times = [t0, t1, ...]` with optional `key_scale`,
+//! `key_rotate` and `key_translate` lists holding one `[x, y, z]` per time,
+//! interpolated in between. Moving objects are not sampled as lights.
+//!
+//! The object- and material-ID render passes number `[[object]]` and
+//! `[[material]]` tables from 1 in file order; materials read from MTL files
+//! are numbered after the scene's own, and 0 means "nothing hit".
+
+use crate::accel::{Blas, Instance, Tlas};
+use crate::background::{self, Background};
+use crate::camera::Camera;
+use crate::checkpoint;
+use crate::hittable::{Hittable, HittableList, Tagged};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::obj;
+use crate::planar::{Cuboid, Disk, Plane, Quad};
+use crate::quadric::{Cone, Cylinder, Paraboloid, Torus};
+use crate::scene_parser::{self, Document, Entry, Table, Value};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::transform::{Keyframe, Keyframed, Transform, Transformed};
+use crate::texture::{CheckerTexture, ImageTexture, NoisePattern, NoiseTexture, SolidColor, Texture};
+use crate::triangle::{Triangle, TriangleMesh};
+use crate::vec3::{Color, Point3, Vec3};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Source of the built-in scene, see [`Scene::builtin`].
+pub const DEFAULT_SCENE: &str = include_str!("../scenes/default.toml");
+
+pub struct Scene {
+    /// Everything that can be hit: one instance per `[[object]]`, or per
+    /// copy for `scatter` objects. Call [`Tlas::rebuild`] after moving any.
+    pub world: Tlas,
+    /// Emissive objects (also present in `world`), sampled directly for lighting.
+    pub lights: HittableList,
+    pub camera: CameraSettings,
+    pub background: Background,
+    pub render: RenderOptions,
+    /// Hash of the scene-file source, identifying the scene in render
+    /// checkpoints. `None` for scenes assembled in code, which can only be
+    /// checkpointed once the caller sets a hash that identifies them.
+    pub source_hash: Option<u64>,
+}
+
+/// A named material from the scene file.
+#[derive(Clone)]
+struct SceneMaterial {
+    mat: Arc<dyn Material + Send + Sync>,
+    emissive: bool,
+    /// Value of the material-ID pass: 1-based position among the `[[material]]` tables.
+    id: u32,
+}
+
+/// Primitives of one object sharing a material, with that material's ID.
+type MaterialGroup = (u32, Vec<Arc<dyn Hittable>>);
+
+/// Camera placement; the aspect ratio is only known once the image size is.
+#[derive(Clone, Debug)]
+pub struct CameraSettings {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub vfov: f64,
+    pub aperture: f64,
+    /// Defaults to the distance between `lookfrom` and `lookat`.
+    pub focus_dist: Option<f64>,
+    /// Times the shutter opens and closes; equal for no motion blur.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl CameraSettings {
+    pub fn build(&self, aspect_ratio: f64) -> Camera {
+        let focus_dist = self.focus_dist.unwrap_or_else(|| (self.lookfrom - self.lookat).length());
+        Camera::new(self.lookfrom, self.lookat, self.vup, self.vfov, aspect_ratio, self.aperture, focus_dist)
+            .with_shutter(self.shutter_open, self.shutter_close)
+    }
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            lookfrom: Point3::new(0.0, 0.0, 0.0),
+            lookat: Point3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 90.0,
+            aperture: 0.0,
+            focus_dist: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+}
+
+/// Render settings stored in the scene. Command-line flags take precedence.
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub samples: Option<u32>,
+    pub max_depth: Option<u32>,
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+    /// 1-based line in the scene file, when the error can be tied to one.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl SceneError {
+    fn at(line: usize, message: impl Into<String>) -> Self {
+        Self { line: Some(line), message: message.into() }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+    /// Loads and builds the scene described by the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path).map_err(|e| SceneError { line: None, message: e.to_string() })?;
+        Self::parse_in(&src, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Builds a scene from scene-file source text. Relative file references
+    /// are resolved against the current directory.
+    pub fn parse(src: &str) -> Result<Self, SceneError> {
+        Self::parse_in(src, Path::new(""))
+    }
+
+    /// Like [`Scene::parse`], resolving relative file references against `base_dir`.
+    pub fn parse_in(src: &str, base_dir: &Path) -> Result<Self, SceneError> {
+        let doc = scene_parser::parse(src).map_err(|e| SceneError::at(e.line, e.message))?;
+        let mut scene = build(&doc, base_dir)?;
+        scene.source_hash = Some(checkpoint::fnv1a(src.as_bytes()));
+        Ok(scene)
+    }
+
+    /// The scene rendered when no `--scene` is given.
+    pub fn builtin() -> Self {
+        Self::parse(DEFAULT_SCENE).expect("built-in scene is valid")
+    }
+}
+
+fn build(doc: &Document, base_dir: &Path) -> Result<Scene, SceneError> {
+    if let Some(entry) = doc.root.entries.first() {
+        return Err(SceneError::at(entry.line, format!("key '{}' must be inside a table such as [camera] or [[object]]", entry.key)));
+    }
+    for (table, is_array) in &doc.sections {
+        let known = if *is_array { ["texture", "material", "prototype", "object"].contains(&table.name.as_str()) } else { ["camera", "render", "background"].contains(&table.name.as_str()) };
+        if !known {
+            let header = if *is_array { format!("[[{}]]", table.name) } else { format!("[{}]", table.name) };
+            return Err(SceneError::at(table.line, format!("unknown section {}", header)));
+        }
+    }
+
+    let camera = match doc.table("camera") {
+        Some(t) => camera_settings(t)?,
+        None => CameraSettings::default(),
+    };
+    let background = match doc.table("background") {
+        Some(t) => background(t)?,
+        None => Background::default(),
+    };
+    let render = match doc.table("render") {
+        Some(t) => render_options(t)?,
+        None => RenderOptions::default(),
+    };
+
+    let mut textures: HashMap<String, Arc<dyn Texture>> = HashMap::new();
+    for table in doc.array("texture") {
+        let mut f = Fields::new(table);
+        let name = f.string("name")?;
+        let name_line = f.line("name");
+        let kind = f.string("type")?;
+        let texture: Arc<dyn Texture> = match kind.as_str() {
+            "solid" => Arc::new(SolidColor::new(f.vec3("color")?)),
+            "checker" => {
+                let scale = f.opt_f64("scale")?.unwrap_or(1.0);
+                Arc::new(CheckerTexture::new(scale, f.texture("even", &textures)?, f.texture("odd", &textures)?))
+            }
+            "image" => {
+                let file = f.string("file")?;
+                Arc::new(ImageTexture::load(base_dir.join(&file)).map_err(|e| f.error("file", format!("cannot load '{}': {}", file, e)))?)
+            }
+            "noise" | "turbulence" | "marble" => {
+                let pattern = match kind.as_str() {
+                    "noise" => NoisePattern::Noise,
+                    "turbulence" => NoisePattern::Turbulence,
+                    _ => NoisePattern::Marble,
+                };
+                Arc::new(NoiseTexture::new(pattern, f.opt_f64("scale")?.unwrap_or(1.0)))
+            }
+            other => return Err(f.error("type", format!("unknown texture type '{}'", other))),
+        };
+        f.finish()?;
+        if textures.insert(name.clone(), texture).is_some() {
+            return Err(SceneError::at(name_line, format!("duplicate texture '{}'", name)));
+        }
+    }
+
+    let mut materials: HashMap<String, SceneMaterial> = HashMap::new();
+    for (index, table) in doc.array("material").enumerate() {
+        let mut f = Fields::new(table);
+        let name = f.string("name")?;
+        let name_line = f.line("name");
+        let kind = f.string("type")?;
+        let mat: Arc<dyn Material + Send + Sync> = match kind.as_str() {
+            "lambertian" => Arc::new(Lambertian::from_texture(f.texture("albedo", &textures)?)),
+            "metal" => Arc::new(Metal::from_texture(f.texture("albedo", &textures)?, f.opt_f64("fuzz")?.unwrap_or(0.0))),
+            "dielectric" => Arc::new(Dielectric::new(f.f64("ir")?)),
+            "diffuse_light" => Arc::new(DiffuseLight::from_texture(f.texture("emit", &textures)?)),
+            other => return Err(f.error("type", format!("unknown material type '{}'", other))),
+        };
+        f.finish()?;
+        let emissive = kind == "diffuse_light";
+        if materials.insert(name.clone(), SceneMaterial { mat, emissive, id: index as u32 + 1 }).is_some() {
+            return Err(SceneError::at(name_line, format!("duplicate material '{}'", name)));
+        }
+    }
+
+    // MTL materials are numbered after the scene's own.
+    let mut next_material_id = materials.len() as u32 + 1;
+
+    let mut prototypes: HashMap<String, Prototype> = HashMap::new();
+    for table in doc.array("prototype") {
+        let mut f = Fields::new(table);
+        let name = f.string("name")?;
+        let name_line = f.line("name");
+        let kind = f.string("type")?;
+        let transform = f.opt_transform()?;
+        let (parts, emissive) = object_parts(&mut f, &kind, &materials, base_dir, &mut next_material_id)?;
+        f.finish()?;
+
+        let (object, lights) = placed_parts(parts, emissive);
+        if prototypes.insert(name.clone(), Prototype { object, transform, lights }).is_some() {
+            return Err(SceneError::at(name_line, format!("duplicate prototype '{}'", name)));
+        }
+    }
+
+    let mut instances = Vec::new();
+    let mut lights = HittableList::new();
+    for (index, table) in doc.array("object").enumerate() {
+        let mut f = Fields::new(table);
+        let kind = f.string("type")?;
+        let object_id = index as u32 + 1;
+        let transform = f.opt_transform()?;
+
+        if kind == "instance" || kind == "scatter" {
+            let name = f.string("prototype")?;
+            let prototype = prototypes.get(&name).ok_or_else(|| f.error("prototype", format!("unknown prototype '{}'", name)))?;
+            let placements = if kind == "scatter" { scatter(&mut f)? } else { vec![None] };
+            f.finish()?;
+            for placement in placements {
+                let transform = compose(compose(prototype.transform, placement), transform);
+                for light in &prototype.lights {
+                    lights.add(transformed(light.clone(), transform));
+                }
+                instances.push(Instance::new(prototype.object.clone(), transform, object_id));
+            }
+            continue;
+        }
+
+        let keyframes = f.opt_keyframes()?;
+        let keyframes_line = f.line("key_times");
+        let (parts, emissive) = object_parts(&mut f, &kind, &materials, base_dir, &mut next_material_id)?;
+        f.finish()?;
+        let (object, object_lights) = placed_parts(parts, emissive);
+        if let Some(keys) = keyframes {
+            if transform.is_some() {
+                return Err(SceneError::at(keyframes_line, "an object moved by keyframes cannot also take scale, rotate or translate"));
+            }
+            let moving = Keyframed::new(object, keys).ok_or_else(|| SceneError::at(keyframes_line, "keyframed scale factors must be positive"))?;
+            // Moving objects are not sampled as lights.
+            instances.push(Instance::new(Arc::new(moving), None, object_id));
+            continue;
+        }
+        for light in object_lights {
+            lights.add(transformed(light, transform));
+        }
+        instances.push(Instance::new(object, transform, object_id));
+    }
+
+    Ok(Scene { world: Tlas::new(instances), lights, camera, background, render, source_hash: None })
+}
+
+/// Material groups of an `[[object]]` or `[[prototype]]` table of type
+/// `kind`, and whether its material emits light.
+fn object_parts(
+    f: &mut Fields,
+    kind: &str,
+    materials: &HashMap<String, SceneMaterial>,
+    base_dir: &Path,
+    next_material_id: &mut u32,
+) -> Result<(Vec<MaterialGroup>, bool), SceneError> {
+    Ok(match kind {
+        "sphere" => {
+            let center = f.vec3("center")?;
+            let radius = f.f64("radius")?;
+            let m = f.material("material", materials)?;
+            (vec![(m.id, vec![Arc::new(Sphere::new(center, radius, m.mat))])], m.emissive)
+        }
+        // Moving, so not sampled as a light.
+        "moving_sphere" => {
+            let center0 = f.vec3("center0")?;
+            let center1 = f.vec3("center1")?;
+            let time0 = f.opt_f64("time0")?.unwrap_or(0.0);
+            let time1 = f.opt_f64("time1")?.unwrap_or(1.0);
+            let radius = f.f64("radius")?;
+            let m = f.material("material", materials)?;
+            (vec![(m.id, vec![Arc::new(MovingSphere::new(center0, center1, time0, time1, radius, m.mat))])], false)
+        }
+        // Unbounded, so never a sampled light; it still emits when hit.
+        "plane" => {
+            let point = f.vec3("point")?;
+            let normal = f.direction("normal")?;
+            let m = f.material("material", materials)?;
+            (vec![(m.id, vec![Arc::new(Plane::new(point, normal, m.mat))])], false)
+        }
+        "quad" => {
+            let corner = f.vec3("corner")?;
+            let u = f.direction("u")?;
+            let v = f.direction("v")?;
+            if u.cross(&v).length_squared() <= 1e-12 * u.length_squared() * v.length_squared() {
+                return Err(f.error("v", "u and v must not be parallel"));
+            }
+            let m = f.material("material", materials)?;
+            (vec![(m.id, vec![Arc::new(Quad::new(corner, u, v, m.mat))])], m.emissive)
+        }
+        "disk" => {
+            let center = f.vec3("center")?;
+            let normal = f.direction("normal")?;
+            let radius = f.positive_f64("radius")?;
+            let m = f.material("material", materials)?;
+            (vec![(m.id, vec![Arc::new(Disk::new(center, normal, radius, m.mat))])], m.emissive)
+        }
+        "box" => {
+            let min = f.vec3("min")?;
+            let max = f.vec3("max")?;
+            let m = f.material("material", materials)?;
+            (vec![(m.id, vec![Arc::new(Cuboid::new(min, max, m.mat))])], m.emissive)
+        }
+        // Quadrics can't be sampled, so they are never lights.
+        "cylinder" | "cone" | "paraboloid" => {
+            let base = f.vec3("base")?;
+            let radius = f.positive_f64("radius")?;
+            let height = f.positive_f64("height")?;
+            let capped = f.opt_bool("capped")?.unwrap_or(kind != "paraboloid");
+            let m = f.material("material", materials)?;
+            let shape: Arc<dyn Hittable> = match kind {
+                "cylinder" => Arc::new(Cylinder::new(base, radius, height, capped, m.mat)),
+                "cone" => Arc::new(Cone::new(base, radius, height, capped, m.mat)),
+                _ => Arc::new(Paraboloid::new(base, radius, height, capped, m.mat)),
+            };
+            (vec![(m.id, vec![shape])], false)
+        }
+        "torus" => {
+            let center = f.vec3("center")?;
+            let major_radius = f.positive_f64("major_radius")?;
+            let minor_radius = f.positive_f64("minor_radius")?;
+            if minor_radius >= major_radius {
+                return Err(f.error("minor_radius", "must be smaller than major_radius"));
+            }
+            let m = f.material("material", materials)?;
+            (vec![(m.id, vec![Arc::new(Torus::new(center, major_radius, minor_radius, m.mat))])], false)
+        }
+        "triangle" => {
+            let vertices = f.vec3_list("vertices")?;
+            if vertices.len() != 3 {
+                return Err(f.error("vertices", format!("a triangle needs 3 vertices, found {}", vertices.len())));
+            }
+            let normals = f.opt_vec3_list("normals")?.unwrap_or_default();
+            let uvs = f.opt_uv_list("uvs")?.unwrap_or_default();
+            let m = f.material("material", materials)?;
+            let triangle = if normals.is_empty() && uvs.is_empty() {
+                Triangle::new(vertices[0], vertices[1], vertices[2], m.mat)
+            } else {
+                let mesh = TriangleMesh::new(vertices, normals, uvs, vec![[0, 1, 2]]).map_err(|e| f.error("vertices", e))?;
+                Triangle::from_mesh(Arc::new(mesh), 0, m.mat)
+            };
+            (vec![(m.id, vec![Arc::new(triangle)])], m.emissive)
+        }
+        "mesh" => {
+            let positions = f.vec3_list("positions")?;
+            let indices = f.index_list("indices")?;
+            let normals = f.opt_vec3_list("normals")?.unwrap_or_default();
+            let uvs = f.opt_uv_list("uvs")?.unwrap_or_default();
+            let m = f.material("material", materials)?;
+            let mesh = TriangleMesh::new(positions, normals, uvs, indices).map_err(|e| f.error("indices", e))?;
+            (vec![(m.id, TriangleMesh::triangles(&Arc::new(mesh), m.mat))], m.emissive)
+        }
+        "obj" => {
+            let file = f.string("file")?;
+            let groups = f.opt_string_list("groups")?;
+            let override_mat = f.opt_material("material", materials)?;
+            let mut model = obj::load(base_dir.join(&file)).map_err(|e| f.error("file", e.to_string()))?;
+            if let Some(groups) = groups {
+                if let Some(missing) = groups.iter().find(|g| !model.meshes.iter().any(|m| &m.group == *g)) {
+                    return Err(f.error("groups", format!("'{}' has no group named '{}'", file, missing)));
+                }
+                model.meshes.retain(|m| groups.contains(&m.group));
+            }
+            let fallback: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+            let emissive = override_mat.as_ref().is_some_and(|m| m.emissive);
+            let override_id = override_mat.as_ref().map(|m| m.id);
+            let mut mtl_ids: HashMap<Option<&str>, u32> = HashMap::new();
+            let parts = model
+                .hittables(fallback, override_mat.map(|m| m.mat))
+                .into_iter()
+                .map(|(name, triangles)| {
+                    let id = override_id.unwrap_or_else(|| {
+                        *mtl_ids.entry(name).or_insert_with(|| {
+                            *next_material_id += 1;
+                            *next_material_id - 1
+                        })
+                    });
+                    (id, triangles)
+                })
+                .collect();
+            (parts, emissive)
+        }
+        other => return Err(f.error("type", format!("unknown object type '{}'", other))),
+    })
+}
+
+/// A `[[prototype]]`: geometry shared by the `instance` and `scatter`
+/// objects placing it.
+struct Prototype {
+    object: Arc<dyn Hittable>,
+    transform: Option<Transform>,
+    /// Emissive primitives, in the prototype's own space.
+    lights: Vec<Arc<dyn Hittable>>,
+}
+
+/// The hittable for an object's material groups, with its primitives tagged
+/// by material, and the primitives to sample as lights.
+fn placed_parts(parts: Vec<MaterialGroup>, emissive: bool) -> (Arc<dyn Hittable>, Vec<Arc<dyn Hittable>>) {
+    let mut primitives = Vec::new();
+    let mut lights = Vec::new();
+    for (material_id, objects) in parts {
+        for object in objects {
+            if emissive {
+                lights.push(object.clone());
+            }
+            primitives.push(Arc::new(Tagged::new(object, 0, material_id)) as Arc<dyn Hittable>);
+        }
+    }
+    (group(primitives), lights)
+}
+
+/// The placements of a `scatter` object: `count` copies at uniformly random
+/// points between the corners `min` and `max`, turned about y at random and
+/// scaled by a random factor from the optional `scale_range`. The optional
+/// `seed` picks another arrangement.
+fn scatter(f: &mut Fields) -> Result<Vec<Option<Transform>>, SceneError> {
+    let count = f.u32("count")?;
+    let min = f.vec3("min")?;
+    let max = f.vec3("max")?;
+    let [lo, hi] = f.opt_pair("scale_range")?.unwrap_or([1.0, 1.0]);
+    let seed = f.opt_u64("seed")?.unwrap_or(1);
+    if (0..3).any(|axis| !min[axis].is_finite() || !max[axis].is_finite() || min[axis] > max[axis]) {
+        return Err(f.error("max", "expected finite corners with min <= max on every axis"));
+    }
+    if !lo.is_finite() || !hi.is_finite() || lo <= 0.0 || hi < lo {
+        return Err(f.error("scale_range", "expected [low, high] with 0 < low <= high"));
+    }
+
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    Ok((0..count)
+        .map(|_| {
+            let position = Vec3::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y), rng.gen_range(min.z..=max.z));
+            let s = rng.gen_range(lo..=hi);
+            let placement = Transform::scale(Vec3::new(s, s, s))
+                .expect("scale factors are positive")
+                .then(&Transform::rotate(up, rng.gen_range(0.0..360.0)))
+                .then(&Transform::translate(position));
+            Some(placement)
+        })
+        .collect())
+}
+
+/// `first` followed by `second`, either of which may be absent.
+fn compose(first: Option<Transform>, second: Option<Transform>) -> Option<Transform> {
+    match (first, second) {
+        (Some(a), Some(b)) => Some(a.then(&b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// `object` placed by `transform`, if there is one.
+fn transformed(object: Arc<dyn Hittable>, transform: Option<Transform>) -> Arc<dyn Hittable> {
+    match transform {
+        Some(transform) => Arc::new(Transformed::new(object, transform)),
+        None => object,
+    }
+}
+
+/// One hittable for `primitives`: the primitive itself, or a BVH over them.
+fn group(mut primitives: Vec<Arc<dyn Hittable>>) -> Arc<dyn Hittable> {
+    if primitives.len() == 1 {
+        return primitives.pop().unwrap();
+    }
+    Arc::new(Blas::new(primitives))
+}
+
+fn camera_settings(table: &Table) -> Result<CameraSettings, SceneError> {
+    let mut f = Fields::new(table);
+    let defaults = CameraSettings::default();
+    let settings = CameraSettings {
+        lookfrom: f.opt_vec3("lookfrom")?.unwrap_or(defaults.lookfrom),
+        lookat: f.opt_vec3("lookat")?.unwrap_or(defaults.lookat),
+        vup: f.opt_vec3("vup")?.unwrap_or(defaults.vup),
+        vfov: f.opt_f64("vfov")?.unwrap_or(defaults.vfov),
+        aperture: f.opt_f64("aperture")?.unwrap_or(defaults.aperture),
+        focus_dist: f.opt_f64("focus_dist")?,
+        shutter_open: f.opt_f64("shutter_open")?.unwrap_or(defaults.shutter_open),
+        shutter_close: f.opt_f64("shutter_close")?.unwrap_or(defaults.shutter_close),
+    };
+    if settings.shutter_close < settings.shutter_open {
+        return Err(f.error("shutter_close", "the shutter must close after it opens"));
+    }
+    f.finish()?;
+    Ok(settings)
+}
+
+fn background(table: &Table) -> Result<Background, SceneError> {
+    let mut f = Fields::new(table);
+    let color = f.opt_vec3("color")?;
+    let horizon = f.opt_vec3("horizon")?;
+    let zenith = f.opt_vec3("zenith")?;
+    f.finish()?;
+
+    match (color, horizon, zenith) {
+        (Some(c), None, None) => Ok(Background::Solid(c)),
+        (Some(_), _, _) => Err(SceneError::at(table.line, "[background] takes either 'color' or 'horizon'/'zenith', not both")),
+        (None, horizon, zenith) => Ok(Background::Gradient {
+            horizon: horizon.unwrap_or(background::SKY_HORIZON),
+            zenith: zenith.unwrap_or(background::SKY_ZENITH),
+        }),
+    }
+}
+
+fn render_options(table: &Table) -> Result<RenderOptions, SceneError> {
+    let mut f = Fields::new(table);
+    let options = RenderOptions {
+        width: f.opt_u32("width")?,
+        height: f.opt_u32("height")?,
+        samples: f.opt_u32("samples")?,
+        max_depth: f.opt_u32("max_depth")?,
+    };
+    f.finish()?;
+    Ok(options)
+}
+
+/// Typed access to the keys of one table; `finish` rejects keys nobody asked for.
+struct Fields<'a> {
+    table: &'a Table,
+    used: Vec<&'a str>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(table: &'a Table) -> Self {
+        Self { table, used: Vec::new() }
+    }
+
+    fn header(&self) -> String {
+        match self.table.name.as_str() {
+            name @ ("camera" | "render" | "background") => format!("[{}]", name),
+            name => format!("[[{}]]", name),
+        }
+    }
+
+    fn entry(&mut self, key: &str) -> Option<&'a Entry> {
+        let entry = self.table.get(key)?;
+        self.used.push(&entry.key);
+        Some(entry)
+    }
+
+    fn required(&mut self, key: &str) -> Result<&'a Entry, SceneError> {
+        self.entry(key).ok_or_else(|| {
+            SceneError::at(self.table.line, format!("{} is missing required key '{}'", self.header(), key))
+        })
+    }
+
+    fn line(&self, key: &str) -> usize {
+        self.table.get(key).map_or(self.table.line, |e| e.line)
+    }
+
+    fn error(&self, key: &str, message: impl Into<String>) -> SceneError {
+        SceneError::at(self.line(key), format!("key '{}': {}", key, message.into()))
+    }
+
+    fn type_error(entry: &Entry, expected: &str) -> SceneError {
+        SceneError::at(entry.line, format!("key '{}': expected {}, found {}", entry.key, expected, entry.value.type_name()))
+    }
+
+    fn finish(self) -> Result<(), SceneError> {
+        match self.table.entries.iter().find(|e| !self.used.contains(&e.key.as_str())) {
+            Some(e) => Err(SceneError::at(e.line, format!("unknown key '{}' in {}", e.key, self.header()))),
+            None => Ok(()),
+        }
+    }
+
+    fn string(&mut self, key: &str) -> Result<String, SceneError> {
+        let entry = self.required(key)?;
+        match &entry.value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(Self::type_error(entry, "a string")),
+        }
+    }
+
+    fn opt_string_list(&mut self, key: &str) -> Result<Option<Vec<String>>, SceneError> {
+        let Some(entry) = self.entry(key) else { return Ok(None) };
+        match &entry.value {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => Ok(s.clone()),
+                    _ => Err(Self::type_error(entry, "an array of strings")),
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some),
+            _ => Err(Self::type_error(entry, "an array of strings")),
+        }
+    }
+
+    fn f64(&mut self, key: &str) -> Result<f64, SceneError> {
+        let entry = self.required(key)?;
+        entry.value.as_f64().ok_or_else(|| Self::type_error(entry, "a number"))
+    }
+
+    fn opt_f64(&mut self, key: &str) -> Result<Option<f64>, SceneError> {
+        match self.table.get(key) {
+            Some(_) => self.f64(key).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn u32(&mut self, key: &str) -> Result<u32, SceneError> {
+        let entry = self.required(key)?;
+        match entry.value {
+            Value::Integer(i) if i > 0 && i <= u32::MAX as i64 => Ok(i as u32),
+            Value::Integer(_) => Err(SceneError::at(entry.line, format!("key '{}': must be a positive integer", key))),
+            _ => Err(Self::type_error(entry, "an integer")),
+        }
+    }
+
+    fn opt_bool(&mut self, key: &str) -> Result<Option<bool>, SceneError> {
+        let Some(entry) = self.entry(key) else { return Ok(None) };
+        match entry.value {
+            Value::Bool(b) => Ok(Some(b)),
+            _ => Err(Self::type_error(entry, "true or false")),
+        }
+    }
+
+    fn opt_u32(&mut self, key: &str) -> Result<Option<u32>, SceneError> {
+        match self.table.get(key) {
+            Some(_) => self.u32(key).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// A non-negative integer, such as a seed, where zero is as good as any.
+    fn opt_u64(&mut self, key: &str) -> Result<Option<u64>, SceneError> {
+        let Some(entry) = self.entry(key) else { return Ok(None) };
+        match entry.value {
+            Value::Integer(i) if i >= 0 => Ok(Some(i as u64)),
+            Value::Integer(_) => Err(SceneError::at(entry.line, format!("key '{}': must be a non-negative integer", key))),
+            _ => Err(Self::type_error(entry, "an integer")),
+        }
+    }
+
+    fn vec3(&mut self, key: &str) -> Result<Vec3, SceneError> {
+        let entry = self.required(key)?;
+        match &entry.value {
+            Value::Array(items) if items.len() != 3 => {
+                Err(SceneError::at(entry.line, format!("key '{}': expected 3 numbers, found {}", key, items.len())))
+            }
+            value => numbers::<3>(value).map(|[x, y, z]| Vec3::new(x, y, z)).ok_or_else(|| Self::type_error(entry, "an array of 3 numbers")),
+        }
+    }
+
+    /// A vector that gives a direction, so must not be zero.
+    fn direction(&mut self, key: &str) -> Result<Vec3, SceneError> {
+        let v = self.vec3(key)?;
+        if v.length_squared() == 0.0 {
+            return Err(self.error(key, "expected a non-zero vector"));
+        }
+        Ok(v)
+    }
+
+    fn positive_f64(&mut self, key: &str) -> Result<f64, SceneError> {
+        let x = self.f64(key)?;
+        if x <= 0.0 {
+            return Err(self.error(key, format!("expected a positive number, found {}", x)));
+        }
+        Ok(x)
+    }
+
+    /// An array of fixed-size number arrays, e.g. `[[0, 0, 0], [1, 0, 0]]`.
+    fn tuple_list<const N: usize>(&mut self, key: &str) -> Result<Vec<[f64; N]>, SceneError> {
+        let entry = self.required(key)?;
+        let Value::Array(items) = &entry.value else {
+            return Err(Self::type_error(entry, &format!("an array of {}-number arrays", N)));
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                numbers::<N>(item).ok_or_else(|| {
+                    SceneError::at(entry.line, format!("key '{}': element {} must be an array of {} numbers", key, i, N))
+                })
+            })
+            .collect()
+    }
+
+    fn vec3_list(&mut self, key: &str) -> Result<Vec<Vec3>, SceneError> {
+        Ok(self.tuple_list::<3>(key)?.into_iter().map(|[x, y, z]| Vec3::new(x, y, z)).collect())
+    }
+
+    fn opt_vec3_list(&mut self, key: &str) -> Result<Option<Vec<Vec3>>, SceneError> {
+        match self.table.get(key) {
+            Some(_) => self.vec3_list(key).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn opt_uv_list(&mut self, key: &str) -> Result<Option<Vec<(f64, f64)>>, SceneError> {
+        match self.table.get(key) {
+            Some(_) => Ok(Some(self.tuple_list::<2>(key)?.into_iter().map(|[u, v]| (u, v)).collect())),
+            None => Ok(None),
+        }
+    }
+
+    fn index_list(&mut self, key: &str) -> Result<Vec<[usize; 3]>, SceneError> {
+        let line = self.line(key);
+        self.tuple_list::<3>(key)?
+            .into_iter()
+            .map(|tri| {
+                let mut face = [0usize; 3];
+                for (slot, i) in face.iter_mut().zip(tri) {
+                    if i < 0.0 || i.fract() != 0.0 {
+                        return Err(SceneError::at(line, format!("key '{}': vertex indices must be non-negative integers, found {}", key, i)));
+                    }
+                    *slot = i as usize;
+                }
+                Ok(face)
+            })
+            .collect()
+    }
+
+    /// An optional `[a, b]` pair of numbers.
+    fn opt_pair(&mut self, key: &str) -> Result<Option<[f64; 2]>, SceneError> {
+        let Some(entry) = self.entry(key) else { return Ok(None) };
+        numbers::<2>(&entry.value).map(Some).ok_or_else(|| Self::type_error(entry, "an array of 2 numbers"))
+    }
+
+    fn opt_vec3(&mut self, key: &str) -> Result<Option<Vec3>, SceneError> {
+        match self.table.get(key) {
+            Some(_) => self.vec3(key).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The placement given by the optional `scale` (a number or `[x, y, z]`),
+    /// `rotate` (degrees about x, then y, then z) and `translate` keys,
+    /// applied in that order.
+    fn opt_transform(&mut self) -> Result<Option<Transform>, SceneError> {
+        let mut steps = Vec::new();
+        if let Some(entry) = self.entry("scale") {
+            let factors = match entry.value.as_f64() {
+                Some(s) => Vec3::new(s, s, s),
+                None => self.vec3("scale")?,
+            };
+            steps.push(Transform::scale(factors).ok_or_else(|| self.error("scale", "scale factors must be non-zero"))?);
+        }
+        if let Some(angles) = self.opt_vec3("rotate")? {
+            let axes = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)];
+            for (axis, degrees) in axes.into_iter().zip([angles.x, angles.y, angles.z]) {
+                if degrees != 0.0 {
+                    steps.push(Transform::rotate(axis, degrees));
+                }
+            }
+        }
+        if let Some(offset) = self.opt_vec3("translate")? {
+            steps.push(Transform::translate(offset));
+        }
+        Ok(steps.into_iter().reduce(|acc, step| acc.then(&step)))
+    }
+
+    /// The motion given by `key_times` and, one entry per time, the optional
+    /// `key_scale`, `key_rotate` and `key_translate` lists of `[x, y, z]`.
+    fn opt_keyframes(&mut self) -> Result<Option<Vec<Keyframe>>, SceneError> {
+        let Some(entry) = self.entry("key_times") else {
+            for key in ["key_scale", "key_rotate", "key_translate"] {
+                if self.table.get(key).is_some() {
+                    return Err(self.error(key, "needs 'key_times'"));
+                }
+            }
+            return Ok(None);
+        };
+        let times = match &entry.value {
+            Value::Array(items) if !items.is_empty() => {
+                items.iter().map(Value::as_f64).collect::<Option<Vec<_>>>().ok_or_else(|| Self::type_error(entry, "an array of numbers"))?
+            }
+            _ => return Err(Self::type_error(entry, "a non-empty array of numbers")),
+        };
+        let mut component = |key: &str, default: Vec3| -> Result<Vec<Vec3>, SceneError> {
+            match self.opt_vec3_list(key)? {
+                Some(values) if values.len() != times.len() => {
+                    Err(self.error(key, format!("expected {} entries, one per key time, found {}", times.len(), values.len())))
+                }
+                Some(values) => Ok(values),
+                None => Ok(vec![default; times.len()]),
+            }
+        };
+        let scale = component("key_scale", Vec3::new(1.0, 1.0, 1.0))?;
+        let rotate = component("key_rotate", Vec3::zero())?;
+        let translate = component("key_translate", Vec3::zero())?;
+        Ok(Some((0..times.len()).map(|i| Keyframe { time: times[i], scale: scale[i], rotate: rotate[i], translate: translate[i] }).collect()))
+    }
+
+    /// A color: either an `[r, g, b]` array or the name of a texture.
+    fn texture(&mut self, key: &str, textures: &HashMap<String, Arc<dyn Texture>>) -> Result<Arc<dyn Texture>, SceneError> {
+        let entry = self.required(key)?;
+        match &entry.value {
+            Value::String(name) => {
+                textures.get(name).cloned().ok_or_else(|| self.error(key, format!("unknown texture '{}'", name)))
+            }
+            Value::Array(_) => Ok(Arc::new(SolidColor::new(self.vec3(key)?))),
+            _ => Err(Self::type_error(entry, "an [r, g, b] array or a texture name")),
+        }
+    }
+
+    fn material(
+        &mut self,
+        key: &str,
+        materials: &HashMap<String, SceneMaterial>,
+    ) -> Result<SceneMaterial, SceneError> {
+        let name = self.string(key)?;
+        materials.get(&name).cloned().ok_or_else(|| self.error(key, format!("unknown material '{}'", name)))
+    }
+
+    fn opt_material(
+        &mut self,
+        key: &str,
+        materials: &HashMap<String, SceneMaterial>,
+    ) -> Result<Option<SceneMaterial>, SceneError> {
+        match self.table.get(key) {
+            Some(_) => self.material(key, materials).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Reads an array of exactly `N` numbers.
+fn numbers<const N: usize>(value: &Value) -> Option<[f64; N]> {
+    let Value::Array(items) = value else { return None };
+    if items.len() != N {
+        return None;
+    }
+    let mut out = [0.0; N];
+    for (slot, item) in out.iter_mut().zip(items) {
+        *slot = item.as_f64()?;
+    }
+    Some(out)
+}
@@ -0,0 +1,339 @@
+//! A small parser for the TOML subset used by scene files.
+//!
+//! Supported: `# comments`, `[table]` and `[[array-of-tables]]` headers,
+//! `key = value` pairs with strings, integers, floats, booleans and
+//! (possibly multi-line, nested) arrays. Every value remembers the line it
+//! was defined on so scene errors can point at the offending key.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "boolean",
+            Value::Array(_) => "array",
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+/// A `[name]` / `[[name]]` section (or the implicit root table).
+#[derive(Clone, Debug)]
+pub struct Table {
+    pub name: String,
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+impl Table {
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.key == key)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub root: Table,
+    /// Sections in file order. `[[name]]` headers produce one table per occurrence.
+    pub sections: Vec<(Table, bool)>,
+}
+
+impl Document {
+    /// The single `[name]` table, if present.
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.sections.iter().find(|(t, array)| !array && t.name == name).map(|(t, _)| t)
+    }
+
+    /// All `[[name]]` tables, in file order.
+    pub fn array(&self, name: &str) -> impl Iterator<Item = &Table> {
+        let name = name.to_string();
+        self.sections.iter().filter(move |(t, array)| *array && t.name == name).map(|(t, _)| t)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(src: &str) -> Result<Document, ParseError> {
+    Parser { chars: src.chars().collect(), pos: 0, line: 1 }.document()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn err<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError { line: self.line, message: message.into() })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    /// Skips spaces and tabs (not newlines).
+    fn skip_inline_ws(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t') | Some('\r')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skips whitespace, newlines and comments (used inside arrays and between statements).
+    fn skip_all_ws(&mut self) {
+        loop {
+            self.skip_inline_ws();
+            self.skip_comment();
+            if self.peek() == Some('\n') {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Expects the end of a statement: optional comment, then newline or EOF.
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        self.skip_inline_ws();
+        self.skip_comment();
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => self.err(format!("unexpected character '{}' after value", c)),
+        }
+    }
+
+    fn document(mut self) -> Result<Document, ParseError> {
+        let mut doc = Document {
+            root: Table { name: String::new(), line: 1, entries: Vec::new() },
+            sections: Vec::new(),
+        };
+
+        loop {
+            self.skip_all_ws();
+            match self.peek() {
+                None => break,
+                Some('[') => {
+                    let line = self.line;
+                    self.bump();
+                    let is_array = self.peek() == Some('[');
+                    if is_array {
+                        self.bump();
+                    }
+                    self.skip_inline_ws();
+                    let name = self.key()?;
+                    self.skip_inline_ws();
+                    let close = if is_array { "]]" } else { "]" };
+                    for expected in close.chars() {
+                        if self.bump() != Some(expected) {
+                            return Err(ParseError { line, message: format!("expected '{}' to close table header", close) });
+                        }
+                    }
+                    self.end_of_line()?;
+                    if !is_array && doc.sections.iter().any(|(t, a)| !a && t.name == name) {
+                        return Err(ParseError { line, message: format!("duplicate table [{}]", name) });
+                    }
+                    doc.sections.push((Table { name, line, entries: Vec::new() }, is_array));
+                }
+                Some(_) => {
+                    let line = self.line;
+                    let key = self.key()?;
+                    self.skip_inline_ws();
+                    if self.bump() != Some('=') {
+                        return Err(ParseError { line, message: format!("expected '=' after key '{}'", key) });
+                    }
+                    self.skip_inline_ws();
+                    let value = self.value()?;
+                    self.end_of_line()?;
+
+                    let table = match doc.sections.last_mut() {
+                        Some((t, _)) => t,
+                        None => &mut doc.root,
+                    };
+                    if table.get(&key).is_some() {
+                        return Err(ParseError { line, message: format!("duplicate key '{}'", key) });
+                    }
+                    table.entries.push(Entry { key, value, line });
+                }
+            }
+        }
+
+        Ok(doc)
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+        let mut key = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                key.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        if key.is_empty() {
+            return match self.peek() {
+                Some(c) => self.err(format!("expected a key, found '{}'", c)),
+                None => self.err("expected a key, found end of file"),
+            };
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => self.array(),
+            Some('t') | Some('f') => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => self.err(format!("invalid value '{}'", word)),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'i' || c == 'n' => self.number(),
+            Some(c) => self.err(format!("unexpected character '{}' at start of value", c)),
+            None => self.err("expected a value, found end of file"),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.') {
+                word.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        word
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let word = self.word();
+        let cleaned: String = word.chars().filter(|&c| c != '_').collect();
+        if let Ok(i) = cleaned.parse::<i64>() {
+            return Ok(Value::Integer(i));
+        }
+        match cleaned.as_str() {
+            "inf" | "+inf" => return Ok(Value::Float(f64::INFINITY)),
+            "-inf" => return Ok(Value::Float(f64::NEG_INFINITY)),
+            _ => {}
+        }
+        match cleaned.parse::<f64>() {
+            Ok(f) if !f.is_nan() => Ok(Value::Float(f)),
+            _ => self.err(format!("invalid number '{}'", word)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let line = self.line;
+        self.bump(); // opening quote
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(ParseError { line, message: "unterminated string".into() }),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('\\') => s.push('\\'),
+                    Some('"') => s.push('"'),
+                    Some(c) => return self.err(format!("unsupported escape '\\{}'", c)),
+                    None => return Err(ParseError { line, message: "unterminated string".into() }),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        let line = self.line;
+        self.bump(); // '['
+        let mut items = Vec::new();
+        loop {
+            self.skip_all_ws();
+            match self.peek() {
+                Some(']') => {
+                    self.bump();
+                    return Ok(Value::Array(items));
+                }
+                None => return Err(ParseError { line, message: "unterminated array".into() }),
+                _ => {}
+            }
+            items.push(self.value()?);
+            self.skip_all_ws();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {}
+                Some(c) => return self.err(format!("expected ',' or ']' in array, found '{}'", c)),
+                None => return Err(ParseError { line, message: "unterminated array".into() }),
+            }
+        }
+    }
+}
@@ -1,8 +1,12 @@
+use crate::aabb::Aabb;
 use crate::vec3::{Point3, Vec3};
 use crate::hittable::{Hittable, HitRecord};
 use crate::ray::Ray;
 use std::sync::Arc;
 use crate::material::Material;
+use crate::onb::Onb;
+use crate::sampler::Sampler;
+use std::f64::consts::PI;
 
 pub struct Sphere {
     pub center: Point3,
@@ -18,29 +22,140 @@ impl Sphere {
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = r.origin - self.center;
-        let a = r.direction.length_squared();
-        let half_b = oc.dot(&r.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-        let discriminant = half_b * half_b - a * c;
+        hit_sphere(self.center, self.radius, &self.mat, r, t_min, t_max)
+    }
 
-        if discriminant < 0.0 {
-            return None;
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        let extent = Vec3::new(r, r, r);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    /// Uniform over the cone of directions subtended by the sphere. Points
+    /// inside the sphere are not sampled.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let distance_squared = (self.center - *origin).length_squared();
+        if distance_squared <= self.radius * self.radius || self.hit(&Ray::new(*origin, *direction), 0.001, f64::INFINITY).is_none() {
+            return 0.0;
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let direction = self.center - *origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::sample_unit_vector(sampler.get_2d());
         }
 
-        let sqrtd = discriminant.sqrt();
+        let [r1, r2] = sampler.get_2d();
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let z = 1.0 + r2 * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).sqrt();
+        Onb::new(direction).transform(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z))
+    }
+}
+
+/// A sphere moving in a straight line from `center0` at `time0` to
+/// `center1` at `time1`, resting at those positions before and after.
+/// Not sampled as a light.
+pub struct MovingSphere {
+    pub center0: Point3,
+    pub center1: Point3,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub mat: Arc<dyn Material + Send + Sync>,
+}
+
+impl MovingSphere {
+    pub fn new(center0: Point3, center1: Point3, time0: f64, time1: f64, radius: f64, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self { center0, center1, time0, time1, radius, mat }
+    }
+
+    /// Where the sphere is at `time`; held at the ends, like [`crate::transform::Keyframed::at`].
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        let f = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + (self.center1 - self.center0) * f
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_sphere(self.center(r.time), self.radius, &self.mat, r, t_min, t_max)
+    }
+
+    /// Covers the sphere at every time, since it rests outside `time0..=time1`.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        let extent = Vec3::new(r, r, r);
+        let start = Aabb::new(self.center0 - extent, self.center0 + extent);
+        Some(start.surrounding(&Aabb::new(self.center1 - extent, self.center1 + extent)))
+    }
+}
+
+fn hit_sphere(center: Point3, radius: f64, mat: &Arc<dyn Material + Send + Sync>, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    let oc = r.origin - center;
+    let a = r.direction.length_squared();
+    let half_b = oc.dot(&r.direction);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrtd = discriminant.sqrt();
 
-        // Find the nearest root that lies in the acceptable range.
-        let mut root = (-half_b - sqrtd) / a;
+    // Find the nearest root that lies in the acceptable range.
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || root > t_max {
+        root = (-half_b + sqrtd) / a;
         if root < t_min || root > t_max {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || root > t_max {
-                return None;
-            }
+            return None;
         }
+    }
 
-        let p = r.at(root);
-        let outward_normal = (p - self.center) / self.radius;
-        Some(HitRecord::new(p, outward_normal, root, r, self.mat.clone()))
+    let p = r.at(root);
+    let outward_normal = (p - center) / radius;
+    let (u, v) = sphere_uv(&((p - center) / radius.abs()));
+    Some(HitRecord::new(p, outward_normal, root, u, v, r, mat.clone()))
+}
+
+/// Spherical mapping of a point on the unit sphere: `u` is the angle around
+/// the y axis from x = -1, `v` the angle from y = -1 up to y = +1, both in [0, 1].
+fn sphere_uv(p: &Point3) -> (f64, f64) {
+    let theta = (-p.y).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accel::Blas;
+    use crate::material::Lambertian;
+    use crate::vec3::Color;
+
+    #[test]
+    fn moving_sphere_rests_outside_its_time_interval() {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = MovingSphere::new(Point3::new(0.0, 0.0, 0.0), Point3::new(4.0, 0.0, 0.0), 0.25, 0.75, 1.0, mat);
+        assert_eq!(sphere.center(0.0), sphere.center0);
+        assert_eq!(sphere.center(1.0), sphere.center1);
+
+        // Through a BVH, so a sphere outside its box would be culled.
+        let blas = Blas::new(vec![Arc::new(sphere)]);
+        for (time, x) in [(0.0, 0.0), (0.5, 2.0), (1.0, 4.0)] {
+            let r = Ray::with_time(Point3::new(x, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), time);
+            let rec = blas.hit(&r, 0.001, f64::INFINITY).unwrap_or_else(|| panic!("missed at time {}", time));
+            assert!((rec.t - 4.0).abs() < 1e-9);
+        }
     }
 }
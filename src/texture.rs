@@ -0,0 +1,109 @@
+use crate::perlin::Perlin;
+use crate::vec3::{Color, Point3};
+use image::RgbImage;
+use std::path::Path;
+use std::sync::Arc;
+
+pub trait Texture: Send + Sync {
+    /// Color at surface coordinates `(u, v)` of the hit point `p`.
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    pub color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color
+    }
+}
+
+/// 3D checkerboard of `scale`-sized cells alternating between two textures.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { inv_scale: 1.0 / scale, even, odd }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x).floor() as i64;
+        let y = (self.inv_scale * p.y).floor() as i64;
+        let z = (self.inv_scale * p.z).floor() as i64;
+        if (x + y + z) % 2 == 0 { self.even.value(u, v, p) } else { self.odd.value(u, v, p) }
+    }
+}
+
+/// Image looked up by `(u, v)`, with `v = 0` at the bottom row.
+pub struct ImageTexture {
+    image: RgbImage,
+}
+
+impl ImageTexture {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, image::ImageError> {
+        Ok(Self { image: image::open(path)?.to_rgb8() })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        let (width, height) = self.image.dimensions();
+        if width == 0 || height == 0 {
+            return Color::new(0.0, 1.0, 1.0); // debugging cyan
+        }
+
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+        let i = ((u * width as f64) as u32).min(width - 1);
+        let j = ((v * height as f64) as u32).min(height - 1);
+        Color::from_rgb8(self.image.get_pixel(i, j).0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NoisePattern {
+    /// Plain Perlin noise.
+    Noise,
+    /// Several summed octaves of noise.
+    Turbulence,
+    /// Sine stripes along z, phase-shifted by turbulence.
+    Marble,
+}
+
+/// Grey procedural texture driven by Perlin noise.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+    pattern: NoisePattern,
+}
+
+impl NoiseTexture {
+    pub fn new(pattern: NoisePattern, scale: f64) -> Self {
+        Self { noise: Perlin::new(), scale, pattern }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let s = *p * self.scale;
+        let grey = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1.0 + self.noise.noise(&s)),
+            NoisePattern::Turbulence => self.noise.turbulence(&s, 7),
+            NoisePattern::Marble => 0.5 * (1.0 + (s.z + 10.0 * self.noise.turbulence(p, 7)).sin()),
+        };
+        Color::new(1.0, 1.0, 1.0) * grey
+    }
+}
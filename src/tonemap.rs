@@ -0,0 +1,128 @@
+//! Display transform: exposure, tone mapping and the sRGB transfer function.
+
+use crate::vec3::Color;
+use std::fmt;
+use std::str::FromStr;
+
+/// Curve compressing linear scene radiance into the displayable [0, 1] range.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneMapper {
+    /// Clip everything above 1.
+    Clamp,
+    /// `x / (1 + x)`: never clips, but never reaches white either.
+    Reinhard,
+    /// Reinhard rescaled so the white point maps exactly to 1.
+    ExtendedReinhard,
+    /// Narkowicz's fit of the ACES filmic reference rendering transform.
+    Aces,
+    /// John Hable's Uncharted 2 filmic curve.
+    Hable,
+}
+
+impl ToneMapper {
+    pub const ALL: [ToneMapper; 5] =
+        [ToneMapper::Clamp, ToneMapper::Reinhard, ToneMapper::ExtendedReinhard, ToneMapper::Aces, ToneMapper::Hable];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMapper::Clamp => "clamp",
+            ToneMapper::Reinhard => "reinhard",
+            ToneMapper::ExtendedReinhard => "extended-reinhard",
+            ToneMapper::Aces => "aces",
+            ToneMapper::Hable => "hable",
+        }
+    }
+
+    /// Linear radiance that maps to display white, for the curves that have one.
+    pub fn default_white_point(self) -> f64 {
+        match self {
+            ToneMapper::Hable => 11.2,
+            _ => 4.0,
+        }
+    }
+
+    fn map(self, x: f64, white: f64) -> f64 {
+        let x = x.max(0.0);
+        match self {
+            ToneMapper::Clamp => x,
+            ToneMapper::Reinhard => x / (1.0 + x),
+            ToneMapper::ExtendedReinhard => x * (1.0 + x / (white * white)) / (1.0 + x),
+            ToneMapper::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+            ToneMapper::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                hable_partial(x * EXPOSURE_BIAS) / hable_partial(white)
+            }
+        }
+        .clamp(0.0, 1.0)
+    }
+}
+
+fn hable_partial(x: f64) -> f64 {
+    const A: f64 = 0.15; // shoulder strength
+    const B: f64 = 0.50; // linear strength
+    const C: f64 = 0.10; // linear angle
+    const D: f64 = 0.20; // toe strength
+    const E: f64 = 0.02; // toe numerator
+    const F: f64 = 0.30; // toe denominator
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+impl fmt::Display for ToneMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ToneMapper {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ToneMapper::ALL.into_iter().find(|t| t.name() == s).ok_or_else(|| {
+            let names: Vec<&str> = ToneMapper::ALL.iter().map(|t| t.name()).collect();
+            format!("unknown tone mapper '{}' (expected one of: {})", s, names.join(", "))
+        })
+    }
+}
+
+/// Converts linear radiance to display-encoded sRGB.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplayTransform {
+    pub tone_mapper: ToneMapper,
+    /// Exposure adjustment in stops (EV); each stop doubles the brightness.
+    pub exposure: f64,
+    /// Overrides the tone mapper's default white point.
+    pub white_point: Option<f64>,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self { tone_mapper: ToneMapper::Clamp, exposure: 0.0, white_point: None }
+    }
+}
+
+impl DisplayTransform {
+    /// Exposed and tone-mapped color, still linear, in [0, 1].
+    pub fn apply(&self, c: Color) -> Color {
+        let scale = 2f64.powf(self.exposure);
+        let white = self.white_point.unwrap_or_else(|| self.tone_mapper.default_white_point());
+        let map = |x: f64| self.tone_mapper.map(x * scale, white);
+        Color::new(map(c.x), map(c.y), map(c.z))
+    }
+
+    /// Fully display-encoded 8-bit sRGB.
+    pub fn encode(&self, c: Color) -> [u8; 3] {
+        let c = self.apply(c);
+        let quantize = |x: f64| (linear_to_srgb(x) * 255.0).round() as u8;
+        [quantize(c.x), quantize(c.y), quantize(c.z)]
+    }
+}
+
+/// The sRGB opto-electronic transfer function, for values in [0, 1].
+pub fn linear_to_srgb(x: f64) -> f64 {
+    if x <= 0.003_130_8 { 12.92 * x } else { 1.055 * x.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Inverse of [`linear_to_srgb`].
+pub fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.040_45 { x / 12.92 } else { ((x + 0.055) / 1.055).powf(2.4) }
+}
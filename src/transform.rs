@@ -0,0 +1,365 @@
+//! Affine transforms and transformed objects.
+//!
+//! [`Transformed`] places any [`Hittable`] with a [`Transform`]: rays are
+//! taken into the object's space, hits are brought back, normals with the
+//! inverse transpose so they stay perpendicular under non-uniform scaling.
+//! Many `Transformed` can share one object, so copies of a mesh cost a matrix
+//! each rather than a copy of the triangles.
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
+use std::ops::Mul;
+use std::sync::Arc;
+
+/// A 4×4 matrix acting on column vectors, stored row-major.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 { m: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]] };
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        for axis in 0..3 {
+            m.m[axis][3] = offset[axis];
+        }
+        m
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        for axis in 0..3 {
+            m.m[axis][axis] = factors[axis];
+        }
+        m
+    }
+
+    /// Counter-clockwise rotation by `degrees` about `axis`, looking down the
+    /// axis towards the origin.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        Mat4 {
+            m: [
+                [t * a.x * a.x + cos, t * a.x * a.y - sin * a.z, t * a.x * a.z + sin * a.y, 0.0],
+                [t * a.x * a.y + sin * a.z, t * a.y * a.y + cos, t * a.y * a.z - sin * a.x, 0.0],
+                [t * a.x * a.z - sin * a.y, t * a.y * a.z + sin * a.x, t * a.z * a.z + cos, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Self::IDENTITY;
+        for (i, row) in self.m.iter().enumerate() {
+            for (j, &v) in row.iter().enumerate() {
+                t.m[j][i] = v;
+            }
+        }
+        t
+    }
+
+    /// Gauss–Jordan elimination with partial pivoting; `None` if singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let p = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= p;
+                inv[col][j] /= p;
+            }
+            for row in 0..4 {
+                let f = a[row][col];
+                if row != col && f != 0.0 {
+                    for j in 0..4 {
+                        a[row][j] -= f * a[col][j];
+                        inv[row][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4 { m: inv })
+    }
+
+    /// Determinant of the upper-left 3×3 block, the linear part.
+    pub fn linear_determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        self.transform_vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    /// Applies the linear part only, as for directions.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let row = |r: &[f64; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Vec3::new(row(&self.m[0]), row(&self.m[1]), row(&self.m[2]))
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+/// An invertible affine transform together with its inverse.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub matrix: Mat4,
+    pub inverse: Mat4,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { matrix: Mat4::IDENTITY, inverse: Mat4::IDENTITY };
+
+    /// `None` if `matrix` cannot be inverted.
+    pub fn new(matrix: Mat4) -> Option<Self> {
+        Some(Self { matrix, inverse: matrix.inverse()? })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        Self { matrix: Mat4::translation(offset), inverse: Mat4::translation(-offset) }
+    }
+
+    /// `None` if a factor is zero.
+    pub fn scale(factors: Vec3) -> Option<Self> {
+        if factors.x == 0.0 || factors.y == 0.0 || factors.z == 0.0 {
+            return None;
+        }
+        Some(Self { matrix: Mat4::scaling(factors), inverse: Mat4::scaling(Vec3::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z)) })
+    }
+
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        let matrix = Mat4::rotation(axis, degrees);
+        Self { matrix, inverse: matrix.transpose() }
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform { matrix: next.matrix * self.matrix, inverse: self.inverse * next.inverse }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform { matrix: self.inverse, inverse: self.matrix }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Maps a surface normal by the inverse transpose, keeping it perpendicular
+    /// to the transformed surface. The result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    /// Box around the transformed corners of `b`.
+    pub fn bounding_box(&self, b: &Aabb) -> Aabb {
+        let corner = |i: usize| {
+            let pick = |axis: usize| if i & (1 << axis) == 0 { b.min[axis] } else { b.max[axis] };
+            self.point(Point3::new(pick(0), pick(1), pick(2)))
+        };
+        (1..8).fold(Aabb::new(corner(0), corner(0)), |acc, i| acc.surrounding(&Aabb::new(corner(i), corner(i))))
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// An object placed by a transform; an instance when the object is shared.
+pub struct Transformed {
+    pub object: Arc<dyn Hittable>,
+    pub transform: Transform,
+    bbox: Option<Aabb>,
+}
+
+impl Transformed {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let bbox = object.bounding_box().map(|b| transform.bounding_box(&b));
+        Self { object, transform, bbox }
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_transformed(self.object.as_ref(), &self.transform, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    /// A linear map `A` takes the object-space direction `w` to `A w`,
+    /// stretching solid angle by `|det A| / |A w|³`; densities shrink by that.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let local_direction = self.transform.inverse.transform_vector(direction.unit_vector()).unit_vector();
+        let pdf = self.object.pdf_value(&self.transform.inverse.transform_point(*origin), &local_direction);
+        if pdf == 0.0 {
+            return 0.0;
+        }
+        let det = self.transform.matrix.linear_determinant().abs();
+        pdf * self.transform.vector(local_direction).length().powi(3) / det
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let local = self.object.random(&self.transform.inverse.transform_point(*origin), sampler);
+        self.transform.vector(local)
+    }
+}
+
+/// Intersects `object` placed by `transform`. The object-space ray is not
+/// renormalized, so its `t` equals the world ray's.
+pub(crate) fn hit_transformed(object: &dyn Hittable, transform: &Transform, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    let local = Ray::with_time(transform.inverse.transform_point(r.origin), transform.inverse.transform_vector(r.direction), r.time);
+    let mut rec = object.hit(&local, t_min, t_max)?;
+    // The inverse transpose preserves the sign of dot(direction, normal), so `front_face` still holds.
+    rec.p = transform.point(rec.p);
+    rec.normal = transform.normal(rec.normal).unit_vector();
+    Some(rec)
+}
+
+/// One key of a [`Keyframed`] motion: a placement made like the scene file's,
+/// by `scale`, then `rotate` (degrees about x, then y, then z), then `translate`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub scale: Vec3,
+    pub rotate: Vec3,
+    pub translate: Vec3,
+}
+
+impl Keyframe {
+    /// `None` if a scale factor is zero.
+    pub fn transform(&self) -> Option<Transform> {
+        let axes = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)];
+        let mut transform = Transform::scale(self.scale)?;
+        for (axis, degrees) in axes.into_iter().zip([self.rotate.x, self.rotate.y, self.rotate.z]) {
+            if degrees != 0.0 {
+                transform = transform.then(&Transform::rotate(axis, degrees));
+            }
+        }
+        Some(transform.then(&Transform::translate(self.translate)))
+    }
+
+    /// Component-wise blend, `f` = 0 giving `self` and 1 giving `other`.
+    fn lerp(&self, other: &Keyframe, f: f64) -> Keyframe {
+        let mix = |a: Vec3, b: Vec3| a + (b - a) * f;
+        Keyframe {
+            time: self.time + (other.time - self.time) * f,
+            scale: mix(self.scale, other.scale),
+            rotate: mix(self.rotate, other.rotate),
+            translate: mix(self.translate, other.translate),
+        }
+    }
+}
+
+/// Samples per pair of keys when bounding the motion between them.
+const MOTION_BOUND_STEPS: usize = 16;
+
+/// An object moved by keyframes: at a ray's time it is placed by the
+/// component-wise interpolation of the keys around that time, and held at the
+/// first or last key outside them. Not sampled as a light.
+pub struct Keyframed {
+    pub object: Arc<dyn Hittable>,
+    keys: Vec<Keyframe>,
+    bbox: Option<Aabb>,
+}
+
+impl Keyframed {
+    /// Sorts `keys` by time. `None` if there are none or a scale factor is
+    /// not positive, which could pass through zero between keys.
+    pub fn new(object: Arc<dyn Hittable>, mut keys: Vec<Keyframe>) -> Option<Self> {
+        if keys.is_empty() || keys.iter().any(|k| k.scale.x <= 0.0 || k.scale.y <= 0.0 || k.scale.z <= 0.0) {
+            return None;
+        }
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let bbox = object.bounding_box().map(|b| motion_bounds(&b, &keys));
+        Some(Self { object, keys, bbox })
+    }
+
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    /// The placement at `time`.
+    pub fn at(&self, time: f64) -> Keyframe {
+        let next = self.keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keys[0];
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1];
+        }
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        a.lerp(b, (time - a.time) / (b.time - a.time))
+    }
+}
+
+/// Box around `b` at every moment of the motion. Between two samples no
+/// point of the object moves further than the translation, plus the turn
+/// and the change of scale applied to its furthest corner, so padding each
+/// sampled box by that much covers the motion in between.
+fn motion_bounds(b: &Aabb, keys: &[Keyframe]) -> Aabb {
+    let reach = (0..3).map(|axis| b.min[axis].abs().max(b.max[axis].abs()).powi(2)).sum::<f64>().sqrt();
+    let parts = |v: Vec3| [v.x.abs(), v.y.abs(), v.z.abs()];
+    let placed = |k: &Keyframe| k.transform().expect("keyframe scales are positive").bounding_box(b);
+
+    let mut bounds = placed(&keys[0]);
+    for pair in keys.windows(2) {
+        let mut previous = pair[0];
+        for step in 1..=MOTION_BOUND_STEPS {
+            let key = pair[0].lerp(&pair[1], step as f64 / MOTION_BOUND_STEPS as f64);
+            let turn = parts(key.rotate - previous.rotate).iter().sum::<f64>().to_radians();
+            let largest_scale = parts(previous.scale).into_iter().chain(parts(key.scale)).fold(0.0, f64::max);
+            let rescale = parts(key.scale - previous.scale).into_iter().fold(0.0, f64::max);
+            let pad = (key.translate - previous.translate).length() + reach * (turn * largest_scale + rescale);
+            let sampled = placed(&key);
+            let padding = Vec3::new(pad, pad, pad);
+            bounds = bounds.surrounding(&Aabb::new(sampled.min - padding, sampled.max + padding));
+            previous = key;
+        }
+    }
+    bounds
+}
+
+impl Hittable for Keyframed {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let transform = self.at(r.time).transform()?;
+        hit_transformed(self.object.as_ref(), &transform, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
@@ -0,0 +1,163 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
+use std::sync::Arc;
+
+/// Indexed triangle mesh. Vertex attributes are shared by every face that
+/// references them; `normals` and `uvs` are either empty or one per position.
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl TriangleMesh {
+    /// Validates attribute counts and face indices.
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        indices: Vec<[usize; 3]>,
+    ) -> Result<Self, String> {
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(format!("mesh has {} positions but {} normals", positions.len(), normals.len()));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(format!("mesh has {} positions but {} uvs", positions.len(), uvs.len()));
+        }
+        if let Some((face, idx)) = indices
+            .iter()
+            .enumerate()
+            .find_map(|(f, tri)| tri.iter().find(|&&i| i >= positions.len()).map(|&i| (f, i)))
+        {
+            return Err(format!("face {} references vertex {} but the mesh has {} positions", face, idx, positions.len()));
+        }
+        Ok(Self { positions, normals, uvs, indices })
+    }
+
+    /// One [`Triangle`] per face, all sharing this mesh's vertex buffers.
+    pub fn triangles(mesh: &Arc<Self>, mat: Arc<dyn Material + Send + Sync>) -> Vec<Arc<dyn Hittable>> {
+        (0..mesh.indices.len())
+            .map(|face| Arc::new(Triangle::from_mesh(mesh.clone(), face, mat.clone())) as Arc<dyn Hittable>)
+            .collect()
+    }
+}
+
+/// A single face of a [`TriangleMesh`], intersected with Möller–Trumbore.
+///
+/// Faces wind counter-clockwise when seen from the front. With per-vertex
+/// normals the shading normal is interpolated; otherwise the face is flat.
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+    pub mat: Arc<dyn Material + Send + Sync>,
+}
+
+impl Triangle {
+    /// A standalone flat triangle.
+    pub fn new(a: Point3, b: Point3, c: Point3, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        let mesh = TriangleMesh { positions: vec![a, b, c], normals: Vec::new(), uvs: Vec::new(), indices: vec![[0, 1, 2]] };
+        Self::from_mesh(Arc::new(mesh), 0, mat)
+    }
+
+    pub fn from_mesh(mesh: Arc<TriangleMesh>, face: usize, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self { mesh, face, mat }
+    }
+
+    fn vertices(&self) -> (Point3, Point3, Point3) {
+        let [i0, i1, i2] = self.mesh.indices[self.face];
+        (self.mesh.positions[i0], self.mesh.positions[i1], self.mesh.positions[i2])
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (v0, v1, v2) = self.vertices();
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+
+        let pvec = r.direction.cross(&edge2);
+        let det = edge1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return None; // ray parallel to the triangle plane
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.origin - v0;
+        let b1 = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(&edge1);
+        let b2 = r.direction.dot(&qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(&qvec) * inv_det;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let b0 = 1.0 - b1 - b2;
+
+        let [i0, i1, i2] = self.mesh.indices[self.face];
+        let geometric_normal = edge1.cross(&edge2).unit_vector();
+        let normal = if self.mesh.normals.is_empty() {
+            geometric_normal
+        } else {
+            let n = &self.mesh.normals;
+            let shading = (n[i0] * b0 + n[i1] * b1 + n[i2] * b2).unit_vector();
+            // Keep the shading normal on the same side as the face it belongs to.
+            if shading.dot(&geometric_normal) < 0.0 { -shading } else { shading }
+        };
+
+        let (u, v) = if self.mesh.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            let uv = &self.mesh.uvs;
+            (
+                uv[i0].0 * b0 + uv[i1].0 * b1 + uv[i2].0 * b2,
+                uv[i0].1 * b0 + uv[i1].1 * b1 + uv[i2].1 * b2,
+            )
+        };
+
+        Some(HitRecord::new(r.at(t), normal, t, u, v, r, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (v0, v1, v2) = self.vertices();
+        // Pad so axis-aligned triangles don't produce a zero-thickness box.
+        let pad = Vec3::new(1e-6, 1e-6, 1e-6);
+        let bbox = Aabb::new(v0, v1).surrounding(&Aabb::new(v2, v2));
+        Some(Aabb::new(bbox.min - pad, bbox.max + pad))
+    }
+
+    /// Area sampling converted to solid angle: `distance² / (cos θ · area)`.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let Some(rec) = self.hit(&Ray::new(*origin, *direction), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let (v0, v1, v2) = self.vertices();
+        let face_normal = (v1 - v0).cross(&(v2 - v0));
+        let area = 0.5 * face_normal.length();
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(&face_normal) / (direction.length() * face_normal.length())).abs();
+        if cosine < 1e-8 {
+            return 0.0;
+        }
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (v0, v1, v2) = self.vertices();
+        let [r1, r2] = sampler.get_2d();
+        let s = r1.sqrt();
+        let point = v0 * (1.0 - s) + v1 * (s * (1.0 - r2)) + v2 * (s * r2);
+        point - *origin
+    }
+}
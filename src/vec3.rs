@@ -1,5 +1,7 @@
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign};
 use rand::Rng;
+use crate::tonemap::srgb_to_linear;
+use std::f64::consts::PI;
 
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Vec3 {
@@ -44,13 +46,11 @@ impl Vec3 {
         *self / self.length()
     }
 
-    pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
         Self::new(rng.r#gen::<f64>(), rng.r#gen::<f64>(), rng.r#gen::<f64>())
     }
 
-    pub fn random_range(min: f64, max: f64) -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn random_range<R: Rng + ?Sized>(rng: &mut R, min: f64, max: f64) -> Self {
         Self::new(
             rng.r#gen_range(min..max),
             rng.r#gen_range(min..max),
@@ -58,26 +58,29 @@ impl Vec3 {
         )
     }
 
-    pub fn random_in_unit_sphere() -> Self {
-        loop {
-            let p = Self::random_range(-1.0, 1.0);
-            if p.length_squared() < 1.0 {
-                return p;
-            }
-        }
+    /// Uniformly distributed unit vector from a 2D sample in [0, 1)².
+    pub fn sample_unit_vector(u: [f64; 2]) -> Self {
+        let z = 1.0 - 2.0 * u[0];
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u[1];
+        Self::new(r * phi.cos(), r * phi.sin(), z)
     }
 
-    pub fn random_unit_vector() -> Self {
-        Self::random_in_unit_sphere().unit_vector()
+    /// Uniformly distributed point inside the unit sphere: a direction from `u`
+    /// and a radius from `w`.
+    pub fn sample_in_unit_sphere(u: [f64; 2], w: f64) -> Self {
+        Self::sample_unit_vector(u) * w.cbrt()
     }
 
-    pub fn random_in_hemisphere(normal: &Self) -> Self {
-        let in_unit_sphere = Self::random_in_unit_sphere();
-        if in_unit_sphere.dot(normal) > 0.0 {
-            in_unit_sphere
-        } else {
-            -in_unit_sphere
+    /// Uniformly distributed point in the unit disk in the xy plane, using
+    /// Shirley's concentric mapping so nearby samples stay nearby.
+    pub fn sample_unit_disk(u: [f64; 2]) -> Self {
+        let (a, b) = (2.0 * u[0] - 1.0, 2.0 * u[1] - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Self::zero();
         }
+        let (r, theta) = if a.abs() > b.abs() { (a, PI / 4.0 * (b / a)) } else { (b, PI / 2.0 - PI / 4.0 * (a / b)) };
+        Self::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 
     pub fn reflect(v: &Self, n: &Self) -> Self {
@@ -171,33 +174,28 @@ impl Neg for Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {}", axis),
+        }
+    }
+}
+
 // Helpful conversions for colors
 impl Color {
-    pub fn to_rgb8(&self, samples_per_pixel: u32) -> [u8; 3] {
-        let mut r = self.x;
-        let mut g = self.y;
-        let mut b = self.z;
-
-        // divide the color by the number of samples and gamma-correct for gamma=2.0
-        let scale = 1.0 / samples_per_pixel as f64;
-        r = (r * scale).sqrt();
-        g = (g * scale).sqrt();
-        b = (b * scale).sqrt();
-
-        [
-            (256.0 * clamp(r, 0.0, 0.999)) as u8,
-            (256.0 * clamp(g, 0.0, 0.999)) as u8,
-            (256.0 * clamp(b, 0.0, 0.999)) as u8,
-        ]
+    /// Decodes 8-bit sRGB values to linear.
+    pub fn from_rgb8(px: [u8; 3]) -> Color {
+        let decode = |c: u8| srgb_to_linear(c as f64 / 255.0);
+        Color::new(decode(px[0]), decode(px[1]), decode(px[2]))
     }
-}
 
-fn clamp(x: f64, min: f64, max: f64) -> f64 {
-    if x < min {
-        min
-    } else if x > max {
-        max
-    } else {
-        x
+    /// Relative luminance with Rec. 709 primaries.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
     }
 }